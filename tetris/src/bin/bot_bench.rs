@@ -0,0 +1,72 @@
+//! Plays headless bot games over a range of seeds to compare heuristic
+//! weights, e.g.
+//!
+//! ```text
+//! cargo run --release -p tetris --bin bot_bench -- --games 20 --pieces 500 --weights -0.51,-0.36,-0.18,0.76
+//! ```
+//!
+//! `--randomizer <name>` deals the pieces with another randomizer than
+//! 7-bags.
+
+use tetris::bot::{play_headless, Bot, Weights};
+use tetris::engine::Dimensions;
+use tetris::randomizer::RandomizerKind;
+
+const DEFAULT_GAMES: u64 = 10;
+const DEFAULT_PIECES: u32 = 1000;
+
+/// The value following `flag` on the command line.
+fn arg_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let i = args.iter().position(|arg| arg == flag)?;
+    return args.get(i + 1).cloned();
+}
+
+fn main() {
+    let games = arg_value("--games")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_GAMES);
+    let pieces = arg_value("--pieces")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_PIECES);
+    let weights = match arg_value("--weights") {
+        Some(text) => match Weights::parse(&text) {
+            Ok(weights) => weights,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        None => Weights::default(),
+    };
+    let kind = match arg_value("--randomizer") {
+        Some(name) => match RandomizerKind::from_name(&name) {
+            Some(kind) => kind,
+            None => {
+                eprintln!("unknown randomizer {}", name);
+                std::process::exit(1);
+            }
+        },
+        None => RandomizerKind::Bag7,
+    };
+    let bot = Bot::new(weights);
+    println!("{:?}", weights);
+    let mut total_lines = 0;
+    let mut total_pieces = 0;
+    for seed in (0..games).into_iter() {
+        let outcome = play_headless(&bot, Dimensions::default(), kind, seed, pieces);
+        println!(
+            "seed {}: {} pieces, {} lines, {} points",
+            seed, outcome.pieces, outcome.lines, outcome.score
+        );
+        total_lines += outcome.lines as u64;
+        total_pieces += outcome.pieces as u64;
+    }
+    if games > 0 {
+        println!(
+            "average: {:.1} pieces, {:.1} lines",
+            total_pieces as f64 / games as f64,
+            total_lines as f64 / games as f64
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! A bot that searches every placement of the falling mino and picks the one
+//! leaving the best board by a weighted heuristic, looking one mino of the
+//! preview ahead.
+//!
+//! The bot only sends ordinary `Input`s, so its games are recorded and
+//! replayed like a player's.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::engine::{Board, Cell, Delays, Dimensions, Game, Input, Mino};
+use crate::randomizer::RandomizerKind;
+
+/// Inputs searched from every position, besides hard drop.
+const MOVES: [Input; 6] = [
+    Input::MoveLeft,
+    Input::MoveRight,
+    Input::SoftDrop,
+    Input::RotateCw,
+    Input::RotateCcw,
+    Input::Rotate180,
+];
+
+/// How much each feature of a board counts. Features that make a board worse
+/// have negative weights.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Weights {
+    /// Per cell of the summed column heights.
+    pub height: f64,
+    /// Per empty cell with a filled cell somewhere above it.
+    pub holes: f64,
+    /// Per cell of height difference between neighbouring columns.
+    pub bumpiness: f64,
+    /// Per line cleared.
+    pub lines: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            height: -0.510066,
+            holes: -0.35663,
+            bumpiness: -0.184483,
+            lines: 0.760666,
+        }
+    }
+}
+
+impl Weights {
+    /// Parses `height,holes,bumpiness,lines`, e.g. `-0.5,-0.36,-0.18,0.76`.
+    pub fn parse(text: &str) -> Result<Weights, String> {
+        let values: Vec<f64> = text
+            .split(',')
+            .map(|w| w.trim().parse().ok())
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("invalid weights `{}`", text))?;
+        match values[..] {
+            [height, holes, bumpiness, lines] => Ok(Weights {
+                height: height,
+                holes: holes,
+                bumpiness: bumpiness,
+                lines: lines,
+            }),
+            _ => Err(format!("expected 4 weights, found `{}`", text)),
+        }
+    }
+}
+
+/// A position a mino can rest at, and the inputs taking it there from where
+/// it is, ending with a hard drop.
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub mino: Mino,
+    pub inputs: Vec<Input>,
+}
+
+/// Every position `mino` can reach and rest at on `board`, each reached with
+/// as few inputs as possible. Tucks and spins are found as well as drops.
+pub fn placements(board: &Board, mino: &Mino) -> Vec<Placement> {
+    let key = |m: &Mino| (m.position(), m.rotation());
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    seen.insert(key(mino));
+    queue.push_back((*mino, vec![]));
+    let mut landed = HashSet::new();
+    let mut found = vec![];
+    while let Some((current, inputs)) = queue.pop_front() {
+        if current.is_grounded(board) && landed.insert(cells(&current)) {
+            let mut drop_inputs = inputs.clone();
+            drop_inputs.push(Input::HardDrop);
+            found.push(Placement {
+                mino: current,
+                inputs: drop_inputs,
+            });
+        }
+        for &input in MOVES.iter() {
+            if let Some(next) = current.moved(input, board) {
+                if seen.insert(key(&next)) {
+                    let mut next_inputs = inputs.clone();
+                    next_inputs.push(input);
+                    queue.push_back((next, next_inputs));
+                }
+            }
+        }
+    }
+    return found;
+}
+
+/// Every position `mino` lands at when rotated, shifted and dropped straight
+/// down. Much cheaper than `placements`, for looking ahead.
+fn drops(board: &Board, mino: &Mino) -> Vec<Mino> {
+    let turns = [
+        None,
+        Some(Input::RotateCw),
+        Some(Input::Rotate180),
+        Some(Input::RotateCcw),
+    ];
+    let mut landed = HashSet::new();
+    let mut found = vec![];
+    for turn in turns.iter() {
+        let turned = match turn {
+            Some(input) => match mino.moved(*input, board) {
+                Some(turned) => turned,
+                None => continue,
+            },
+            None => *mino,
+        };
+        for &side in [Input::MoveLeft, Input::MoveRight].iter() {
+            let mut shifted = Some(turned);
+            while let Some(current) = shifted {
+                let landing = current.moved(Input::HardDrop, board).unwrap();
+                if landed.insert(cells(&landing)) {
+                    found.push(landing);
+                }
+                shifted = current.moved(side, board);
+            }
+        }
+    }
+    return found;
+}
+
+fn cells(mino: &Mino) -> Vec<(i32, i32)> {
+    let mut cells: Vec<(i32, i32)> = mino.get_blocks().iter().map(|b| (b.x, b.y)).collect();
+    cells.sort();
+    return cells;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bot {
+    weights: Weights,
+}
+
+impl Bot {
+    pub fn new(weights: Weights) -> Self {
+        Bot { weights: weights }
+    }
+
+    pub fn weights(&self) -> Weights {
+        return self.weights;
+    }
+
+    /// The placement of the falling mino of `game` leaving the best board
+    /// once the next mino is dropped too, or `None` if it cannot rest
+    /// anywhere.
+    pub fn choose(&self, game: &Game) -> Option<Placement> {
+        let board = game.board();
+        let next = game.preview(1).first().map(|m| **m);
+        let mut best: Option<(f64, Placement)> = None;
+        for placement in placements(board, game.mino()) {
+            let mut after = board.clone();
+            let lines = after.place(&placement.mino).len();
+            let value = match next {
+                Some(next) => self.best_drop(&after, &next, lines),
+                None => self.evaluate(&after, lines),
+            };
+            if best
+                .as_ref()
+                .map_or(true, |(best_value, _)| value > *best_value)
+            {
+                best = Some((value, placement));
+            }
+        }
+        return best.map(|(_, placement)| placement);
+    }
+
+    /// The value of the best board `mino` can be dropped into, with `lines`
+    /// already cleared. A mino that cannot spawn ends the game.
+    fn best_drop(&self, board: &Board, mino: &Mino, lines: usize) -> f64 {
+        let blocked = mino
+            .get_blocks()
+            .iter()
+            .any(|b| !board.is_blank_at(b.x, b.y));
+        if blocked {
+            return f64::NEG_INFINITY;
+        }
+        return drops(board, mino)
+            .iter()
+            .map(|landing| {
+                let mut after = board.clone();
+                let more = after.place(landing).len();
+                self.evaluate(&after, lines + more)
+            })
+            .fold(f64::NEG_INFINITY, f64::max);
+    }
+
+    /// Scores `board` after clearing `lines` lines; higher is better.
+    pub fn evaluate(&self, board: &Board, lines: usize) -> f64 {
+        let heights = column_heights(board);
+        let height: usize = heights.iter().sum();
+        let bumpiness: usize = heights
+            .windows(2)
+            .map(|w| (w[0] as i64 - w[1] as i64).abs() as usize)
+            .sum();
+        let holes = count_holes(board, &heights);
+        return self.weights.height * height as f64
+            + self.weights.holes * holes as f64
+            + self.weights.bumpiness * bumpiness as f64
+            + self.weights.lines * lines as f64;
+    }
+}
+
+/// Height of each column: one more than its highest filled row.
+fn column_heights(board: &Board) -> Vec<usize> {
+    let rows = board.dimensions().total_height() as i32;
+    return (0..board.width() as i32)
+        .into_iter()
+        .map(|x| {
+            (0..rows)
+                .into_iter()
+                .rev()
+                .find(|&y| board.cell_at(x, y) != Some(Cell::Empty))
+                .map_or(0, |y| y as usize + 1)
+        })
+        .collect();
+}
+
+fn count_holes(board: &Board, heights: &[usize]) -> usize {
+    let mut holes = 0;
+    for (x, &height) in heights.iter().enumerate() {
+        for y in (0..height).into_iter() {
+            if board.is_blank_at(x as i32, y as i32) {
+                holes += 1;
+            }
+        }
+    }
+    return holes;
+}
+
+/// How a headless game went.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Outcome {
+    pub pieces: u32,
+    pub lines: u32,
+    pub score: u64,
+}
+
+/// Lets `bot` play a game dealt by `kind` seeded with `seed` as fast as it
+/// can, without delays, until it tops out or has locked `max_pieces` minos.
+pub fn play_headless(
+    bot: &Bot,
+    dimensions: Dimensions,
+    kind: RandomizerKind,
+    seed: u64,
+    max_pieces: u32,
+) -> Outcome {
+    let mut game = Game::with_dimensions(dimensions, kind, seed);
+    game.set_delays(Delays::none());
+    while game.is_active() && game.pieces() < max_pieces {
+        let placement = match bot.choose(&game) {
+            Some(placement) => placement,
+            None => break,
+        };
+        for input in placement.inputs {
+            game.apply(input);
+        }
+    }
+    return Outcome {
+        pieces: game.pieces(),
+        lines: game.score().lines(),
+        score: game.score().points(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Shape;
+
+    /// The cells of the mino `game` locked last.
+    fn locked_cells(game: &Game) -> Vec<(i32, i32)> {
+        return cells(game.last_locked().unwrap());
+    }
+
+    #[test]
+    fn every_placement_is_reached_by_its_inputs() {
+        // An overhang on the left, so some placements need a tuck or spin.
+        let text = "\
+            ###.......\n\
+            ..........\n\
+            #.##..####\n\
+            ##.#..####";
+        let board = Board::from_text(Dimensions::default(), text).unwrap();
+        for &shape in [Shape::T, Shape::S, Shape::I, Shape::O].iter() {
+            let game = Game::with_sequence(board.clone(), vec![shape]);
+            let found = placements(game.board(), game.mino());
+            assert!(!found.is_empty());
+            for placement in found {
+                let mut game = Game::with_sequence(board.clone(), vec![shape]);
+                game.set_delays(Delays::none());
+                for &input in placement.inputs.iter() {
+                    game.apply(input);
+                }
+                assert_eq!(game.pieces(), 1);
+                assert_eq!(locked_cells(&game), cells(&placement.mino));
+            }
+        }
+    }
+
+    #[test]
+    fn placements_include_tucks_under_an_overhang() {
+        let text = "\
+            ###.......\n\
+            ..........\n\
+            ..........";
+        let board = Board::from_text(Dimensions::default(), text).unwrap();
+        let game = Game::with_sequence(board.clone(), vec![Shape::O]);
+        let found = placements(game.board(), game.mino());
+        assert!(found
+            .iter()
+            .any(|p| cells(&p.mino) == vec![(0, 0), (0, 1), (1, 0), (1, 1)]));
+    }
+
+    #[test]
+    fn plays_a_headless_game_the_same_every_time() {
+        let bot = Bot::new(Weights::default());
+        let play = || play_headless(&bot, Dimensions::default(), RandomizerKind::Bag7, 5, 100);
+        let outcome = play();
+        assert_eq!(outcome.pieces, 100);
+        assert!(outcome.lines >= 30);
+        assert_eq!(play(), outcome);
+    }
+
+    #[test]
+    fn parses_weights() {
+        let weights = Weights::parse("-0.5, -0.25,-0.125,1").unwrap();
+        assert_eq!(weights.holes, -0.25);
+        assert_eq!(weights.lines, 1.0);
+        assert!(Weights::parse("-0.5,-0.25,-0.125").is_err());
+        assert!(Weights::parse("a,b,c,d").is_err());
+    }
+}
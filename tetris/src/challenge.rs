@@ -0,0 +1,275 @@
+//! Puzzle mode: works through the puzzles of a directory in order, started
+//! with `--puzzles [dir]`. Solved puzzles are remembered between sessions,
+//! and play resumes at the first unsolved one.
+
+use nannou::prelude::*;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::{Action, Bindings};
+use crate::engine::{Delays, Game, Input};
+use crate::handling::{AutoRepeat, Handling};
+use crate::puzzle::{puzzle_paths, Outcome, Progress, Puzzle};
+use crate::replay::FRAME_TIME;
+
+use super::{
+    arg_value, board_draw, draw_board, draw_ghost, draw_mino, due_frames, frame_inputs, key_event,
+    key_names, load_config, release_key, show_hold, show_lines, show_next, show_overlay, KeyEvent,
+    State, DEFAULT_PREVIEW_LEN, T_BG,
+};
+
+const DEFAULT_PUZZLE_DIR: &str = "puzzles";
+const PROGRESS_PATH: &str = "tetris-puzzles.progress";
+
+pub struct Model {
+    /// Each puzzle with the file name it is remembered by.
+    puzzles: Vec<(String, Puzzle)>,
+    current: usize,
+    game: Game,
+    outcome: Option<Outcome>,
+    progress: Progress,
+    delays: Delays,
+    all_spin: bool,
+    handling: Handling,
+    frame_timer: Duration,
+    held_keys: HashSet<Key>,
+    auto_repeat: AutoRepeat,
+    bindings: Bindings,
+    state: State,
+}
+
+pub fn model(_app: &App) -> Model {
+    let config = load_config();
+    let dir = arg_value("--puzzles")
+        .filter(|arg| !arg.starts_with("--"))
+        .unwrap_or_else(|| DEFAULT_PUZZLE_DIR.to_string());
+    let puzzles = load_puzzles(&dir);
+    if puzzles.is_empty() {
+        eprintln!("no puzzles found in {}", dir);
+        std::process::exit(1);
+    }
+    let progress = match Progress::load(PROGRESS_PATH) {
+        Ok(progress) => progress,
+        Err(err) => {
+            eprintln!("could not load {}: {}", PROGRESS_PATH, err);
+            Progress::default()
+        }
+    };
+    let current = puzzles
+        .iter()
+        .position(|(name, _)| !progress.is_solved(name))
+        .unwrap_or(0);
+    let mut model = Model {
+        game: puzzles[current].1.new_game(),
+        puzzles: puzzles,
+        current: current,
+        outcome: None,
+        progress: progress,
+        delays: config.delays,
+        all_spin: config.all_spin,
+        handling: config.handling,
+        frame_timer: Duration::from_secs(0),
+        held_keys: HashSet::new(),
+        auto_repeat: AutoRepeat::new(config.handling),
+        bindings: config.bindings,
+        state: State::Playing,
+    };
+    start_puzzle(&mut model, current);
+    return model;
+}
+
+/// Every readable puzzle in `dir`, skipping the others with a warning.
+fn load_puzzles(dir: &str) -> Vec<(String, Puzzle)> {
+    let paths = match puzzle_paths(dir) {
+        Ok(paths) => paths,
+        Err(err) => {
+            eprintln!("could not read {}: {}", dir, err);
+            return vec![];
+        }
+    };
+    let mut puzzles = vec![];
+    for path in paths {
+        match Puzzle::load(&path) {
+            Ok(puzzle) => puzzles.push((file_name(&path), puzzle)),
+            Err(err) => eprintln!("skipping {}: {}", path.display(), err),
+        }
+    }
+    return puzzles;
+}
+
+fn file_name(path: &Path) -> String {
+    return path
+        .file_name()
+        .map_or(String::new(), |n| n.to_string_lossy().into_owned());
+}
+
+fn start_puzzle(model: &mut Model, index: usize) {
+    model.current = index;
+    model.game = model.puzzles[index].1.new_game();
+    model.game.set_delays(model.delays);
+    model.game.set_all_spin(model.all_spin);
+    model.outcome = None;
+    model.auto_repeat = AutoRepeat::new(model.handling);
+    model.frame_timer = Duration::from_secs(0);
+    model.state = State::Playing;
+}
+
+/// Ends the attempt once the puzzle is solved or lost, remembering solved
+/// puzzles.
+fn check_outcome(model: &mut Model) {
+    let (name, puzzle) = &model.puzzles[model.current];
+    let outcome = match puzzle.outcome(&model.game) {
+        Some(outcome) => outcome,
+        None => return,
+    };
+    if outcome == Outcome::Solved && model.progress.solve(name) {
+        if let Err(err) = model.progress.save(PROGRESS_PATH) {
+            eprintln!("could not save {}: {}", PROGRESS_PATH, err);
+        }
+    }
+    model.outcome = Some(outcome);
+    model.state = State::GameOver;
+}
+
+/// Applies an input and checks the puzzle right away, so no piece past the
+/// last one can be played.
+fn apply_input(model: &mut Model, input: Input) {
+    if model.state != State::Playing {
+        return;
+    }
+    model.game.apply(input);
+    check_outcome(model);
+}
+
+fn press_action(model: &mut Model, action: Action) {
+    match action.input() {
+        Some(input) => {
+            if let Some(input) = model.auto_repeat.press(input) {
+                apply_input(model, input);
+            }
+        }
+        None => match (action, model.state) {
+            (Action::Pause, State::Playing) => model.state = State::Paused,
+            (Action::Pause, State::Paused) => model.state = State::Playing,
+            (Action::Restart, _) => start_puzzle(model, model.current),
+            _ => {}
+        },
+    }
+}
+
+fn key_pressed(model: &mut Model, key: Key) {
+    let last = model.puzzles.len() - 1;
+    match key {
+        Key::Return if model.outcome == Some(Outcome::Solved) && model.current < last => {
+            start_puzzle(model, model.current + 1)
+        }
+        Key::LBracket if model.current > 0 => start_puzzle(model, model.current - 1),
+        Key::RBracket if model.current < last => start_puzzle(model, model.current + 1),
+        _ => {}
+    }
+}
+
+pub fn event(_app: &App, model: &mut Model, event: Event) {
+    match event {
+        Event::WindowEvent {
+            id: _,
+            simple: Some(we),
+        } => window_event(model, we),
+        _other => {}
+    }
+}
+
+fn window_event(model: &mut Model, event: WindowEvent) {
+    match key_event(&mut model.held_keys, event) {
+        Some(KeyEvent::Pressed(key)) => match model.bindings.action(key) {
+            Some(action) => press_action(model, action),
+            None => key_pressed(model, key),
+        },
+        Some(KeyEvent::Released(key)) => release_key(&model.bindings, &mut model.auto_repeat, key),
+        None => {}
+    }
+}
+
+pub fn update(_app: &App, model: &mut Model, update: Update) {
+    if model.state != State::Playing {
+        return;
+    }
+    for _ in (0..due_frames(&mut model.frame_timer, update.since_last)).into_iter() {
+        for input in frame_inputs(&mut model.auto_repeat, &model.game) {
+            apply_input(model, input);
+        }
+        model.game.step(FRAME_TIME);
+        check_outcome(model);
+        if model.state != State::Playing {
+            return;
+        }
+    }
+}
+
+pub fn view(app: &App, model: &Model, frame: Frame) {
+    frame.clear(T_BG);
+    let (name, puzzle) = &model.puzzles[model.current];
+    let game = &model.game;
+    let board = game.board();
+    let draw = board_draw(app, board.dimensions());
+    draw_board(board, game.phase(), game.delays(), &draw);
+    if game.is_active() && game.is_falling() {
+        draw_ghost(&game.ghost(), &draw);
+        draw_mino(game.mino(), &draw);
+    }
+    show_hold(game.hold(), game.can_hold(), board, &draw);
+    // Only the puzzle's own pieces, not those dealt again after the last.
+    let shown = puzzle.remaining(game).min(DEFAULT_PREVIEW_LEN);
+    show_next(&game.preview(shown), board, &draw);
+    let mut lines = vec![
+        format!("PUZZLE {}/{}", model.current + 1, model.puzzles.len()),
+        puzzle.name().to_uppercase(),
+        puzzle.goal().description(),
+        format!("PIECES LEFT {}", puzzle.remaining(game)),
+    ];
+    if model.progress.is_solved(name) {
+        lines.push("SOLVED BEFORE".to_string());
+    }
+    show_lines(&lines, board, &draw);
+    let restart_key = key_names(&model.bindings, Action::Restart);
+    let pause_key = key_names(&model.bindings, Action::Pause);
+    match (model.state, model.outcome) {
+        (State::Paused, _) => show_overlay(
+            &[
+                "PAUSED".to_string(),
+                format!("Press {} to resume", pause_key),
+                "[ / ]: previous / next puzzle".to_string(),
+            ],
+            board,
+            &draw,
+        ),
+        (State::GameOver, Some(Outcome::Solved)) => {
+            let next = if model.current + 1 < model.puzzles.len() {
+                "Return: next puzzle".to_string()
+            } else {
+                "ALL PUZZLES DONE".to_string()
+            };
+            show_overlay(
+                &[
+                    "SOLVED".to_string(),
+                    next,
+                    format!("{}: try again", restart_key),
+                ],
+                board,
+                &draw,
+            );
+        }
+        (State::GameOver, _) => show_overlay(
+            &[
+                "FAILED".to_string(),
+                puzzle.goal().description(),
+                format!("{}: try again", restart_key),
+            ],
+            board,
+            &draw,
+        ),
+        _ => {}
+    }
+    draw.to_frame(app, &frame).unwrap();
+}
@@ -0,0 +1,604 @@
+//! Key bindings and handling settings, read from a TOML file at startup.
+//!
+//! Every section and action is optional and falls back to the defaults, e.g.
+//!
+//! ```toml
+//! [bindings]
+//! move_left = ["J", "Left"]
+//! move_right = ["L", "Right"]
+//! rotate_180 = ["E"]
+//!
+//! [handling]
+//! das_ms = 120
+//! arr_ms = 0
+//! sdf = 40
+//!
+//! [board]
+//! width = 4
+//! height = 20
+//! hidden_rows = 4
+//!
+//! [delays]
+//! are_ms = 0
+//! line_clear_ms = 200
+//!
+//! [rules]
+//! all_spin = true
+//!
+//! [versus.player1]
+//! hard_drop = ["Space"]
+//!
+//! [versus.player2]
+//! hold = ["RControl"]
+//! ```
+//!
+//! The `[versus.*]` tables bind the two players of local versus, sharing one
+//! keyboard, so no key may be bound for both.
+//!
+//! Key names are those of nannou's `Key`, such as `A`, `Key1`, `Space`,
+//! `LShift` or `Left`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use nannou::prelude::Key;
+use serde::Deserialize;
+
+use crate::engine::{Delays, Dimensions, Input};
+use crate::handling::Handling;
+
+/// Everything a key can be bound to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Hold,
+    Pause,
+    Restart,
+}
+
+pub const ACTIONS: [Action; 10] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::RotateCw,
+    Action::RotateCcw,
+    Action::Rotate180,
+    Action::Hold,
+    Action::Pause,
+    Action::Restart,
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Pause => "pause",
+            Action::Restart => "restart",
+            _ => self.input().unwrap().name(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        return ACTIONS.iter().copied().find(|a| a.name() == name);
+    }
+
+    /// The game input this action sends, if it is not handled by the
+    /// front-end itself.
+    pub fn input(&self) -> Option<Input> {
+        match self {
+            Action::MoveLeft => Some(Input::MoveLeft),
+            Action::MoveRight => Some(Input::MoveRight),
+            Action::SoftDrop => Some(Input::SoftDrop),
+            Action::HardDrop => Some(Input::HardDrop),
+            Action::RotateCw => Some(Input::RotateCw),
+            Action::RotateCcw => Some(Input::RotateCcw),
+            Action::Rotate180 => Some(Input::Rotate180),
+            Action::Hold => Some(Input::Hold),
+            Action::Pause | Action::Restart => None,
+        }
+    }
+
+    fn default_keys(&self) -> Vec<Key> {
+        match self {
+            Action::MoveLeft => vec![Key::A, Key::Left],
+            Action::MoveRight => vec![Key::D, Key::Right],
+            Action::SoftDrop => vec![Key::S, Key::Down],
+            Action::HardDrop => vec![Key::W],
+            Action::RotateCw => vec![Key::R, Key::Up],
+            Action::RotateCcw => vec![Key::Q],
+            Action::Rotate180 => vec![Key::E],
+            Action::Hold => vec![Key::C, Key::LShift],
+            Action::Pause => vec![Key::P, Key::Escape],
+            Action::Restart => vec![Key::Space],
+        }
+    }
+
+    /// Keys of `player` (0 or 1) in local versus, one hand each side of the
+    /// keyboard. Only the first player can pause and restart the match.
+    fn versus_keys(&self, player: usize) -> Vec<Key> {
+        let key = match (player, self) {
+            (0, Action::MoveLeft) => Key::A,
+            (0, Action::MoveRight) => Key::D,
+            (0, Action::SoftDrop) => Key::S,
+            (0, Action::HardDrop) => Key::W,
+            (0, Action::RotateCw) => Key::E,
+            (0, Action::RotateCcw) => Key::Q,
+            (0, Action::Rotate180) => Key::R,
+            (0, Action::Hold) => Key::LShift,
+            (0, Action::Pause) => Key::Escape,
+            (0, Action::Restart) => Key::Space,
+            (_, Action::MoveLeft) => Key::Left,
+            (_, Action::MoveRight) => Key::Right,
+            (_, Action::SoftDrop) => Key::Down,
+            (_, Action::HardDrop) => Key::Up,
+            (_, Action::RotateCw) => Key::Period,
+            (_, Action::RotateCcw) => Key::Comma,
+            (_, Action::Rotate180) => Key::Slash,
+            (_, Action::Hold) => Key::RShift,
+            (_, Action::Pause) | (_, Action::Restart) => return vec![],
+        };
+        return vec![key];
+    }
+}
+
+/// Maps each bound key to its action.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings {
+    keys: HashMap<Key, Action>,
+}
+
+impl Bindings {
+    pub fn action(&self, key: Key) -> Option<Action> {
+        return self.keys.get(&key).copied();
+    }
+
+    /// The keys bound to `action`, in a stable order.
+    pub fn keys(&self, action: Action) -> Vec<Key> {
+        let mut keys: Vec<Key> = self
+            .keys
+            .iter()
+            .filter(|(_, a)| **a == action)
+            .map(|(k, _)| *k)
+            .collect();
+        keys.sort();
+        return keys;
+    }
+
+    /// Binds each action to its keys, reporting every key bound to more than
+    /// one action.
+    fn from_keys(action_keys: &[(Action, Vec<Key>)]) -> Result<Bindings, Vec<String>> {
+        let mut keys = HashMap::new();
+        let mut errors = vec![];
+        for (action, action_key_list) in action_keys.iter() {
+            for &key in action_key_list.iter() {
+                match keys.insert(key, *action) {
+                    Some(other) if other != *action => errors.push(format!(
+                        "key {:?} is bound to both {} and {}",
+                        key,
+                        other.name(),
+                        action.name()
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        return Ok(Bindings { keys: keys });
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let action_keys: Vec<(Action, Vec<Key>)> =
+            ACTIONS.iter().map(|a| (*a, a.default_keys())).collect();
+        return Bindings::from_keys(&action_keys).unwrap();
+    }
+}
+
+/// The default bindings of both players in local versus.
+fn default_versus_bindings() -> [Bindings; 2] {
+    let player = |p: usize| {
+        let action_keys: Vec<(Action, Vec<Key>)> =
+            ACTIONS.iter().map(|a| (*a, a.versus_keys(p))).collect();
+        Bindings::from_keys(&action_keys).unwrap()
+    };
+    return [player(0), player(1)];
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub bindings: Bindings,
+    pub handling: Handling,
+    pub dimensions: Dimensions,
+    pub delays: Delays,
+    /// Whether pieces other than T score spins.
+    pub all_spin: bool,
+    /// Bindings of each player in local versus.
+    pub versus_bindings: [Bindings; 2],
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bindings: Bindings::default(),
+            handling: Handling::default(),
+            dimensions: Dimensions::default(),
+            delays: Delays::default(),
+            all_spin: false,
+            versus_bindings: default_versus_bindings(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "{}", err),
+            ConfigError::Parse(err) => write!(f, "{}", err),
+            ConfigError::Invalid(errors) => write!(f, "{}", errors.join("; ")),
+        }
+    }
+}
+
+/// The file as written, before names are resolved and defaults filled in.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    bindings: HashMap<String, Vec<String>>,
+    handling: HandlingFile,
+    board: BoardFile,
+    delays: DelaysFile,
+    rules: RulesFile,
+    versus: VersusFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct VersusFile {
+    player1: HashMap<String, Vec<String>>,
+    player2: HashMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct HandlingFile {
+    das_ms: Option<u64>,
+    arr_ms: Option<u64>,
+    sdf: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct BoardFile {
+    width: Option<usize>,
+    height: Option<usize>,
+    hidden_rows: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DelaysFile {
+    are_ms: Option<u64>,
+    line_clear_ms: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RulesFile {
+    all_spin: Option<bool>,
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        return Config::from_toml(&text);
+    }
+
+    fn from_toml(text: &str) -> Result<Config, ConfigError> {
+        let file: ConfigFile = toml::from_str(text).map_err(ConfigError::Parse)?;
+        let mut errors = vec![];
+        let action_keys = resolve_keys(&file.bindings, |a| a.default_keys(), &mut errors);
+        let versus_keys = [
+            resolve_keys(&file.versus.player1, |a| a.versus_keys(0), &mut errors),
+            resolve_keys(&file.versus.player2, |a| a.versus_keys(1), &mut errors),
+        ];
+        for (_, keys) in versus_keys[0].iter() {
+            for key in keys.iter() {
+                if versus_keys[1].iter().any(|(_, other)| other.contains(key)) {
+                    errors.push(format!("key {:?} is bound for both versus players", key));
+                }
+            }
+        }
+        if file.handling.sdf == Some(0) {
+            errors.push("sdf must be at least 1".to_string());
+        }
+        let default_dimensions = Dimensions::default();
+        let dimensions = Dimensions {
+            width: file.board.width.unwrap_or(default_dimensions.width),
+            height: file.board.height.unwrap_or(default_dimensions.height),
+            hidden_rows: file
+                .board
+                .hidden_rows
+                .unwrap_or(default_dimensions.hidden_rows),
+        };
+        if let Err(board_errors) = dimensions.validate() {
+            errors.extend(board_errors);
+        }
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+        let bindings = Bindings::from_keys(&action_keys).map_err(ConfigError::Invalid)?;
+        let versus_bindings = [
+            Bindings::from_keys(&versus_keys[0]).map_err(ConfigError::Invalid)?,
+            Bindings::from_keys(&versus_keys[1]).map_err(ConfigError::Invalid)?,
+        ];
+        let default = Handling::default();
+        let handling = Handling {
+            das: file
+                .handling
+                .das_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.das),
+            arr: file
+                .handling
+                .arr_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.arr),
+            sdf: file.handling.sdf.unwrap_or(default.sdf),
+        };
+        let default_delays = Delays::default();
+        let delays = Delays {
+            are: file
+                .delays
+                .are_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default_delays.are),
+            line_clear: file
+                .delays
+                .line_clear_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default_delays.line_clear),
+        };
+        return Ok(Config {
+            bindings: bindings,
+            handling: handling,
+            dimensions: dimensions,
+            delays: delays,
+            all_spin: file.rules.all_spin.unwrap_or(false),
+            versus_bindings: versus_bindings,
+        });
+    }
+}
+
+/// The keys of every action, taken from `bindings` as written in the file
+/// where given and from `default_keys` otherwise. Unknown names are added to
+/// `errors`.
+fn resolve_keys<F: Fn(&Action) -> Vec<Key>>(
+    bindings: &HashMap<String, Vec<String>>,
+    default_keys: F,
+    errors: &mut Vec<String>,
+) -> Vec<(Action, Vec<Key>)> {
+    let mut action_keys: Vec<(Action, Vec<Key>)> =
+        ACTIONS.iter().map(|a| (*a, default_keys(a))).collect();
+    for (name, key_names) in bindings.iter() {
+        let action = match Action::from_name(name) {
+            Some(action) => action,
+            None => {
+                errors.push(format!("unknown action `{}`", name));
+                continue;
+            }
+        };
+        let mut keys = vec![];
+        for key_name in key_names.iter() {
+            match key_from_name(key_name) {
+                Some(key) => keys.push(key),
+                None => errors.push(format!("unknown key `{}` for {}", key_name, name)),
+            }
+        }
+        for entry in action_keys.iter_mut() {
+            if entry.0 == action {
+                entry.1 = keys.clone();
+            }
+        }
+    }
+    return action_keys;
+}
+
+const KEYS: [Key; 72] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+    Key::Key0,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::Escape,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::Left,
+    Key::Up,
+    Key::Right,
+    Key::Down,
+    Key::Back,
+    Key::Return,
+    Key::Space,
+    Key::Tab,
+    Key::LShift,
+    Key::RShift,
+    Key::LControl,
+    Key::RControl,
+    Key::LAlt,
+    Key::RAlt,
+    Key::Comma,
+    Key::Period,
+    Key::Slash,
+    Key::Semicolon,
+    Key::Apostrophe,
+    Key::LBracket,
+    Key::RBracket,
+    Key::Minus,
+    Key::Equals,
+];
+
+/// Looks a key up by the name of its `Key` variant.
+fn key_from_name(name: &str) -> Option<Key> {
+    return KEYS.iter().copied().find(|k| format!("{:?}", k) == name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The errors `text` is rejected with.
+    fn errors(text: &str) -> Vec<String> {
+        match Config::from_toml(text) {
+            Err(ConfigError::Invalid(errors)) => errors,
+            Err(err) => vec![err.to_string()],
+            Ok(_) => panic!("`{}` was accepted", text),
+        }
+    }
+
+    #[test]
+    fn empty_file_gives_the_defaults() {
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn reads_every_section() {
+        let text = r#"
+            [bindings]
+            move_left = ["J", "Left"]
+            hold = []
+
+            [handling]
+            das_ms = 120
+            arr_ms = 0
+
+            [board]
+            width = 8
+
+            [delays]
+            are_ms = 0
+
+            [rules]
+            all_spin = true
+
+            [versus.player2]
+            hold = ["RControl"]
+        "#;
+        let config = Config::from_toml(text).unwrap();
+        assert_eq!(
+            config.bindings.keys(Action::MoveLeft),
+            vec![Key::J, Key::Left]
+        );
+        assert_eq!(config.bindings.action(Key::A), None);
+        assert_eq!(config.bindings.keys(Action::Hold), vec![]);
+        assert_eq!(config.bindings.action(Key::R), Some(Action::RotateCw));
+        assert_eq!(config.handling.das, Duration::from_millis(120));
+        assert_eq!(config.handling.arr, Duration::from_secs(0));
+        assert_eq!(config.handling.sdf, Handling::default().sdf);
+        assert_eq!(config.dimensions.width, 8);
+        assert_eq!(config.dimensions.height, Dimensions::default().height);
+        assert_eq!(config.delays.are, Duration::from_secs(0));
+        assert_eq!(config.delays.line_clear, Delays::default().line_clear);
+        assert!(config.all_spin);
+        assert_eq!(
+            config.versus_bindings[1].action(Key::RControl),
+            Some(Action::Hold)
+        );
+        assert_eq!(config.versus_bindings[1].action(Key::RShift), None);
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert_eq!(
+            errors("[bindings]\njump = [\"J\"]"),
+            vec!["unknown action `jump`"]
+        );
+        assert_eq!(
+            errors("[bindings]\nhold = [\"Hyper\"]"),
+            vec!["unknown key `Hyper` for hold"]
+        );
+        assert_eq!(errors("[sound]\nvolume = 3").len(), 1);
+    }
+
+    #[test]
+    fn rejects_a_key_bound_twice() {
+        assert_eq!(
+            errors("[bindings]\nhard_drop = [\"A\"]"),
+            vec!["key A is bound to both move_left and hard_drop"]
+        );
+        assert_eq!(
+            errors("[versus.player2]\nhold = [\"LShift\"]"),
+            vec!["key LShift is bound for both versus players"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert_eq!(
+            errors("[handling]\nsdf = 0"),
+            vec!["sdf must be at least 1"]
+        );
+        assert!(!errors("[board]\nwidth = 2").is_empty());
+        assert_eq!(errors("[handling]\ndas_ms = -5").len(), 1);
+    }
+}
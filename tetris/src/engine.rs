@@ -0,0 +1,2117 @@
+//! Game rules of tetris, independent of any rendering or windowing library.
+//!
+//! A front-end owns a `Game`, feeds it player `Input`s with `apply` and
+//! advances time with `step`, then reads the board and the falling mino back
+//! to draw them.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::randomizer::{Randomizer, RandomizerKind, Sequence};
+
+/// Narrowest board an I mino still fits across.
+pub const MIN_BOARD_WIDTH: usize = 4;
+pub const MAX_BOARD_WIDTH: usize = 40;
+pub const MIN_BOARD_HEIGHT: usize = 4;
+pub const MAX_BOARD_HEIGHT: usize = 60;
+/// Minos spawn in the two rows above the visible ones, so at least those
+/// must be hidden.
+pub const MIN_HIDDEN_ROWS: usize = 2;
+
+/// The most upcoming minos a front-end can ask `Game::preview` for.
+pub const MAX_PREVIEW: usize = 6;
+
+/// How long a grounded mino may keep sliding before it locks.
+const LOCK_DELAY: Duration = Duration::from_millis(500);
+/// How many moves or rotations may restart the lock delay before the mino
+/// locks on touching the stack. Reaching a new lowest row gives them back.
+const MAX_LOCK_RESETS: u32 = 15;
+
+/// Extra garbage sent by a perfect clear.
+const PERFECT_CLEAR_GARBAGE: u32 = 10;
+
+/// Extra garbage sent by the n-th clear of a combo, from the first on.
+const COMBO_GARBAGE: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Input {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Hold,
+}
+
+impl Input {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Input::MoveLeft => "move_left",
+            Input::MoveRight => "move_right",
+            Input::SoftDrop => "soft_drop",
+            Input::HardDrop => "hard_drop",
+            Input::RotateCw => "rotate_cw",
+            Input::RotateCcw => "rotate_ccw",
+            Input::Rotate180 => "rotate_180",
+            Input::Hold => "hold",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Input> {
+        match name {
+            "move_left" => Some(Input::MoveLeft),
+            "move_right" => Some(Input::MoveRight),
+            "soft_drop" => Some(Input::SoftDrop),
+            "hard_drop" => Some(Input::HardDrop),
+            "rotate_cw" => Some(Input::RotateCw),
+            "rotate_ccw" => Some(Input::RotateCcw),
+            "rotate_180" => Some(Input::Rotate180),
+            "hold" => Some(Input::Hold),
+            _ => None,
+        }
+    }
+}
+
+/// Size of a board: `height` visible rows with `hidden_rows` more above
+/// them, where minos spawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Dimensions {
+    pub width: usize,
+    pub height: usize,
+    pub hidden_rows: usize,
+}
+
+impl Default for Dimensions {
+    fn default() -> Self {
+        Dimensions {
+            width: 10,
+            height: 20,
+            hidden_rows: 4,
+        }
+    }
+}
+
+impl Dimensions {
+    /// Every way these dimensions are out of the supported range.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.width < MIN_BOARD_WIDTH || self.width > MAX_BOARD_WIDTH {
+            errors.push(format!(
+                "board width must be between {} and {}",
+                MIN_BOARD_WIDTH, MAX_BOARD_WIDTH
+            ));
+        }
+        if self.height < MIN_BOARD_HEIGHT || self.height > MAX_BOARD_HEIGHT {
+            errors.push(format!(
+                "board height must be between {} and {}",
+                MIN_BOARD_HEIGHT, MAX_BOARD_HEIGHT
+            ));
+        }
+        if self.hidden_rows < MIN_HIDDEN_ROWS {
+            errors.push(format!(
+                "board must have at least {} hidden rows",
+                MIN_HIDDEN_ROWS
+            ));
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        return Ok(());
+    }
+
+    /// Visible and hidden rows together.
+    pub fn total_height(&self) -> usize {
+        return self.height + self.hidden_rows;
+    }
+}
+
+/// Pauses between one mino locking and the next one spawning.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Delays {
+    /// Entry delay (ARE): time before the next mino spawns.
+    pub are: Duration,
+    /// Time full rows stay on the board before they are cleared, on top of
+    /// the entry delay.
+    pub line_clear: Duration,
+}
+
+impl Default for Delays {
+    fn default() -> Self {
+        Delays {
+            are: Duration::from_millis(100),
+            line_clear: Duration::from_millis(300),
+        }
+    }
+}
+
+impl Delays {
+    /// No pause at all: the next mino spawns on the lock itself.
+    pub fn none() -> Self {
+        Delays {
+            are: Duration::from_secs(0),
+            line_clear: Duration::from_secs(0),
+        }
+    }
+}
+
+/// What the game is doing between inputs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Phase {
+    /// A mino is falling and takes inputs.
+    Falling,
+    /// The full rows `rows` are still on the board, waiting out the
+    /// line-clear delay.
+    LineClear { rows: Vec<usize>, elapsed: Duration },
+    /// Waiting out the entry delay before the next mino spawns.
+    Entry { elapsed: Duration },
+}
+
+/// Garbage rows received from an opponent and not yet raised, all with their
+/// hole in the same column.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Garbage {
+    lines: u32,
+    hole: usize,
+}
+
+pub struct Game {
+    mino: Mino,
+    board: Board,
+    next_minos: VecDeque<Mino>,
+    randomizer: Box<dyn Randomizer>,
+    randomizer_kind: RandomizerKind,
+    seed: u64,
+    hold: Option<Mino>,
+    can_hold: bool,
+    score: Score,
+    active: bool,
+    phase: Phase,
+    delays: Delays,
+    /// Whether pieces other than T score spins.
+    all_spin: bool,
+    /// The mino that locked last.
+    last_locked: Option<Mino>,
+    pieces: u32,
+    /// Garbage waiting to rise, oldest first.
+    garbage: VecDeque<Garbage>,
+    garbage_rng: StdRng,
+    /// Garbage sent since the last `take_attack`.
+    attack: u32,
+    sent: u32,
+    elapsed: Duration,
+    gravity_timer: Duration,
+    lock_timer: Duration,
+    lock_resets: u32,
+    lowest_y: i32,
+}
+
+impl Game {
+    /// Starts a game on a board of `dimensions` dealing 7-bags from a random
+    /// seed.
+    pub fn new(dimensions: Dimensions) -> Self {
+        return Game::with_dimensions(dimensions, RandomizerKind::Bag7, rand::random());
+    }
+
+    /// Starts a game on the standard board whose pieces are dealt by `kind`
+    /// seeded with `seed`, so the same arguments always deal the same pieces.
+    pub fn with_seed(kind: RandomizerKind, seed: u64) -> Self {
+        return Game::with_dimensions(Dimensions::default(), kind, seed);
+    }
+
+    /// Like `with_seed`, on a board of `dimensions`, which must be valid.
+    pub fn with_dimensions(dimensions: Dimensions, kind: RandomizerKind, seed: u64) -> Self {
+        return Game::with_board(Board::new(dimensions), kind, seed);
+    }
+
+    /// Like `with_seed`, starting on the cells of `board`. The game is over
+    /// at once if the first mino cannot spawn there.
+    pub fn with_board(board: Board, kind: RandomizerKind, seed: u64) -> Self {
+        let mut game = Game::start(board, kind.build(seed), kind, seed);
+        if !can_spawn(&game.mino, &game.board) {
+            game.active = false;
+        }
+        return game;
+    }
+
+    /// Starts a game on `board` dealing `shapes` in order, then again from
+    /// the first, for puzzles. `shapes` must not be empty. The game reports
+    /// 7-bags and seed 0, so it cannot be replayed.
+    pub fn with_sequence(board: Board, shapes: Vec<Shape>) -> Self {
+        let mut game = Game::start(
+            board,
+            Box::new(Sequence::new(shapes)),
+            RandomizerKind::Bag7,
+            0,
+        );
+        if !can_spawn(&game.mino, &game.board) {
+            game.active = false;
+        }
+        return game;
+    }
+
+    fn start(
+        board: Board,
+        mut randomizer: Box<dyn Randomizer>,
+        kind: RandomizerKind,
+        seed: u64,
+    ) -> Self {
+        let mut minos = VecDeque::new();
+        fill_queue(&mut minos, randomizer.as_mut(), &board);
+        let mino = minos.pop_front().unwrap();
+        fill_queue(&mut minos, randomizer.as_mut(), &board);
+        Game {
+            mino: mino,
+            board: board,
+            next_minos: minos,
+            randomizer: randomizer,
+            randomizer_kind: kind,
+            seed: seed,
+            hold: None,
+            can_hold: true,
+            score: Score::new(),
+            active: true,
+            phase: Phase::Falling,
+            delays: Delays::default(),
+            all_spin: false,
+            last_locked: None,
+            pieces: 0,
+            garbage: VecDeque::new(),
+            garbage_rng: StdRng::seed_from_u64(seed),
+            attack: 0,
+            sent: 0,
+            elapsed: Duration::from_secs(0),
+            gravity_timer: gravity_interval(1),
+            lock_timer: Duration::from_secs(0),
+            lock_resets: 0,
+            lowest_y: mino.y,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    pub fn delays(&self) -> Delays {
+        return self.delays;
+    }
+
+    /// Sets the pauses after each lock, which apply from the next lock on.
+    pub fn set_delays(&mut self, delays: Delays) {
+        self.delays = delays;
+    }
+
+    pub fn all_spin(&self) -> bool {
+        return self.all_spin;
+    }
+
+    /// Turns on spins for every piece: one rotated into a spot it cannot
+    /// move out of scores like a T-spin mini.
+    pub fn set_all_spin(&mut self, all_spin: bool) {
+        self.all_spin = all_spin;
+    }
+
+    pub fn phase(&self) -> &Phase {
+        return &self.phase;
+    }
+
+    /// Whether a mino is falling, rather than the game waiting out a delay.
+    pub fn is_falling(&self) -> bool {
+        return self.phase == Phase::Falling;
+    }
+
+    pub fn last_locked(&self) -> Option<&Mino> {
+        return self.last_locked.as_ref();
+    }
+
+    /// Number of minos locked so far.
+    pub fn pieces(&self) -> u32 {
+        return self.pieces;
+    }
+
+    /// Queues `lines` garbage rows from an opponent, with a hole in a random
+    /// column. They rise at the next lock that clears nothing, unless clears
+    /// cancel them first.
+    pub fn receive_garbage(&mut self, lines: u32) {
+        if lines == 0 {
+            return;
+        }
+        // More than the whole board rises the same as the whole board.
+        let lines = lines.min(self.board.dimensions().total_height() as u32);
+        let hole = self.garbage_rng.gen_range(0..self.board.width());
+        self.garbage.push_back(Garbage {
+            lines: lines,
+            hole: hole,
+        });
+    }
+
+    /// Reseeds the columns of garbage holes, which otherwise follow the seed
+    /// of the pieces, so players dealt the same pieces get different holes.
+    pub fn set_garbage_seed(&mut self, seed: u64) {
+        self.garbage_rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Garbage rows queued and not yet raised.
+    pub fn pending_garbage(&self) -> u32 {
+        return self
+            .garbage
+            .iter()
+            .fold(0, |sum, g| sum.saturating_add(g.lines));
+    }
+
+    /// Takes the garbage to send to the opponent, which has built up since
+    /// the last call.
+    pub fn take_attack(&mut self) -> u32 {
+        let attack = self.attack;
+        self.attack = 0;
+        return attack;
+    }
+
+    /// Garbage rows sent over the whole game.
+    pub fn sent(&self) -> u32 {
+        return self.sent;
+    }
+
+    pub fn randomizer_kind(&self) -> RandomizerKind {
+        return self.randomizer_kind;
+    }
+
+    pub fn mino(&self) -> &Mino {
+        return &self.mino;
+    }
+
+    pub fn board(&self) -> &Board {
+        return &self.board;
+    }
+
+    /// Where the current mino would land if it were dropped straight down.
+    pub fn ghost(&self) -> Mino {
+        return self.mino.landing(&self.board);
+    }
+
+    /// The next `count` minos to spawn, soonest first. `count` is capped at
+    /// `MAX_PREVIEW`.
+    pub fn preview(&self, count: usize) -> Vec<&Mino> {
+        return self
+            .next_minos
+            .iter()
+            .take(count.min(MAX_PREVIEW))
+            .collect();
+    }
+
+    /// The mino set aside with `Input::Hold`, in its spawn position.
+    pub fn hold(&self) -> Option<&Mino> {
+        return self.hold.as_ref();
+    }
+
+    /// Whether `Input::Hold` is available, i.e. no hold was used since the
+    /// last lock.
+    pub fn can_hold(&self) -> bool {
+        return self.can_hold;
+    }
+
+    /// Time the mino currently takes to fall one row.
+    pub fn gravity_interval(&self) -> Duration {
+        return gravity_interval(self.score.level());
+    }
+
+    pub fn score(&self) -> &Score {
+        return &self.score;
+    }
+
+    pub fn is_active(&self) -> bool {
+        return self.active;
+    }
+
+    /// Total time the game has been stepped while active.
+    pub fn elapsed(&self) -> Duration {
+        return self.elapsed;
+    }
+
+    /// Applies `input` to the falling mino. Inputs during delays are
+    /// dropped.
+    pub fn apply(&mut self, input: Input) {
+        if !self.active || !self.is_falling() {
+            return;
+        }
+        let moved = match input {
+            Input::MoveLeft => self.mino.move_left_if_possible(&self.board),
+            Input::MoveRight => self.mino.move_right_if_possible(&self.board),
+            Input::SoftDrop => {
+                if self.move_down() {
+                    self.score.soft_drop(1);
+                }
+                false
+            }
+            Input::HardDrop => {
+                let ghost = self.ghost();
+                self.score.hard_drop((self.mino.y - ghost.y) as u32);
+                self.mino = ghost;
+                self.lock();
+                false
+            }
+            Input::RotateCw => self.mino.rotate_if_possible(&self.board, Rotation::Cw),
+            Input::RotateCcw => self.mino.rotate_if_possible(&self.board, Rotation::Ccw),
+            Input::Rotate180 => self.mino.rotate_if_possible(&self.board, Rotation::Half),
+            Input::Hold => {
+                self.hold_mino();
+                false
+            }
+        };
+        if moved && self.lock_resets < MAX_LOCK_RESETS {
+            self.lock_timer = Duration::from_secs(0);
+            if !self.mino.can_move_down(&self.board) {
+                self.lock_resets += 1;
+            }
+        }
+    }
+
+    fn move_down(&mut self) -> bool {
+        if !self.mino.move_down_if_possible(&self.board) {
+            return false;
+        }
+        if self.mino.y < self.lowest_y {
+            self.lowest_y = self.mino.y;
+            self.lock_resets = 0;
+        }
+        return true;
+    }
+
+    fn hold_mino(&mut self) {
+        if !self.can_hold {
+            return;
+        }
+        let held = self.board.spawn_mino(self.mino.shape);
+        let next_mino = match self.hold.take() {
+            Some(mino) => mino,
+            None => self.get_next_mino(),
+        };
+        self.hold = Some(held);
+        self.can_hold = false;
+        self.spawn(next_mino);
+    }
+
+    fn spawn(&mut self, mino: Mino) {
+        if can_spawn(&mino, &self.board) {
+            self.mino = mino;
+            self.lock_timer = Duration::from_secs(0);
+            self.lock_resets = 0;
+            self.lowest_y = mino.y;
+        } else {
+            self.active = false;
+        }
+    }
+
+    /// Advances the game by `dt`: the mino falls one row per elapsed gravity
+    /// interval of the current level, and locks once it has rested on the
+    /// stack for `LOCK_DELAY`. After a lock, the game waits out the delays
+    /// instead.
+    pub fn step(&mut self, dt: Duration) {
+        if !self.active {
+            return;
+        }
+        self.elapsed += dt;
+        if !self.is_falling() {
+            self.advance_delay(dt);
+            return;
+        }
+        self.gravity_timer += dt;
+        let interval = self.gravity_interval();
+        while self.gravity_timer >= interval {
+            self.gravity_timer -= interval;
+            self.move_down();
+        }
+        if self.mino.can_move_down(&self.board) {
+            self.lock_timer = Duration::from_secs(0);
+            return;
+        }
+        self.lock_timer += dt;
+        if self.lock_timer >= LOCK_DELAY || self.lock_resets >= MAX_LOCK_RESETS {
+            self.lock();
+        }
+    }
+
+    /// Locks the mino and scores it, then starts the delays before the next
+    /// mino. Full rows stay on the board until the line-clear delay is over.
+    fn lock(&mut self) {
+        let tspin = self.mino.tspin(&self.board);
+        let spin = if self.all_spin {
+            self.mino.spin(&self.board)
+        } else {
+            None
+        };
+        self.board.put_mino(&self.mino);
+        self.last_locked = Some(self.mino);
+        self.pieces += 1;
+        let rows = self.board.full_rows();
+        let attack = self.score.lock(Clear {
+            lines: rows.len() as u32,
+            tspin: tspin,
+            spin: spin,
+            perfect: !rows.is_empty() && self.board.is_empty_without(&rows),
+        });
+        let attack = self.cancel_garbage(attack);
+        self.attack = self.attack.saturating_add(attack);
+        self.sent = self.sent.saturating_add(attack);
+        self.can_hold = true;
+        if rows.is_empty() && !self.raise_garbage() {
+            self.active = false;
+            return;
+        }
+        self.phase = if rows.is_empty() {
+            Phase::Entry {
+                elapsed: Duration::from_secs(0),
+            }
+        } else {
+            Phase::LineClear {
+                rows: rows,
+                elapsed: Duration::from_secs(0),
+            }
+        };
+        self.advance_delay(Duration::from_secs(0));
+    }
+
+    /// Uses `attack` to cancel queued garbage first, and returns what is left
+    /// to send.
+    fn cancel_garbage(&mut self, mut attack: u32) -> u32 {
+        while attack > 0 {
+            let front = match self.garbage.front_mut() {
+                Some(front) => front,
+                None => break,
+            };
+            let cancelled = attack.min(front.lines);
+            front.lines -= cancelled;
+            attack -= cancelled;
+            if front.lines == 0 {
+                self.garbage.pop_front();
+            }
+        }
+        return attack;
+    }
+
+    /// Raises all queued garbage into the board. Returns false if that pushed
+    /// blocks out of the top, which ends the game.
+    fn raise_garbage(&mut self) -> bool {
+        let mut fits = true;
+        while let Some(garbage) = self.garbage.pop_front() {
+            fits &= self
+                .board
+                .insert_garbage(garbage.lines as usize, garbage.hole);
+        }
+        return fits;
+    }
+
+    /// Advances the current delay by `dt`, clearing the full rows and then
+    /// spawning the next mino as each delay runs out.
+    fn advance_delay(&mut self, dt: Duration) {
+        let delays = self.delays;
+        match &mut self.phase {
+            Phase::Falling => {}
+            Phase::LineClear { elapsed, .. } => {
+                *elapsed += dt;
+                if *elapsed >= delays.line_clear {
+                    self.board.delete_lines();
+                    self.phase = Phase::Entry {
+                        elapsed: Duration::from_secs(0),
+                    };
+                    self.advance_delay(Duration::from_secs(0));
+                }
+            }
+            Phase::Entry { elapsed } => {
+                *elapsed += dt;
+                if *elapsed >= delays.are {
+                    self.phase = Phase::Falling;
+                    let next_mino: Mino = self.get_next_mino();
+                    self.spawn(next_mino);
+                }
+            }
+        }
+    }
+
+    fn get_next_mino(&mut self) -> Mino {
+        let mino = self.next_minos.pop_front().unwrap();
+        fill_queue(&mut self.next_minos, self.randomizer.as_mut(), &self.board);
+        return mino;
+    }
+}
+
+/// Deals minos until at least `MAX_PREVIEW` are queued, so the preview never
+/// runs short.
+fn fill_queue(minos: &mut VecDeque<Mino>, randomizer: &mut dyn Randomizer, board: &Board) {
+    while minos.len() < MAX_PREVIEW {
+        minos.push_back(board.spawn_mino(randomizer.next_shape()));
+    }
+}
+
+/// Time the mino takes to fall one row at `level`, following the guideline
+/// curve of one second at level 1. The curve stops speeding up at level 20.
+fn gravity_interval(level: u32) -> Duration {
+    let n = (level.min(20) - 1) as i32;
+    let seconds = (0.8 - n as f64 * 0.007).powi(n);
+    return Duration::from_secs_f64(seconds);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+/// What a lock achieved, reported to the HUD when it cleared lines or was a
+/// spin.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Clear {
+    pub lines: u32,
+    pub tspin: TSpin,
+    /// The shape of a piece other than T that was spun into place, when
+    /// all-spins are on.
+    pub spin: Option<Shape>,
+    /// Whether the clear left the board empty.
+    pub perfect: bool,
+}
+
+impl Clear {
+    pub fn name(&self) -> String {
+        let lines = match self.lines {
+            0 => "",
+            1 => "SINGLE",
+            2 => "DOUBLE",
+            3 => "TRIPLE",
+            _ => "TETRIS",
+        };
+        let name = match (self.spin, self.tspin) {
+            (Some(shape), _) => format!("{}-SPIN {}", shape.letter(), lines),
+            (None, TSpin::None) => lines.to_string(),
+            (None, TSpin::Mini) => format!("T-SPIN MINI {}", lines),
+            (None, TSpin::Full) => format!("T-SPIN {}", lines),
+        };
+        return name.trim_end().to_string();
+    }
+
+    /// How the lock scores: spins of other pieces count as T-spin minis.
+    fn scored_tspin(&self) -> TSpin {
+        match self.spin {
+            Some(_) => TSpin::Mini,
+            None => self.tspin,
+        }
+    }
+
+    /// Garbage rows this clear sends in versus, before back-to-back and combo
+    /// bonuses.
+    fn base_garbage(&self) -> u32 {
+        match (self.scored_tspin(), self.lines) {
+            (_, 0) => 0,
+            (TSpin::None, n) if n >= 4 => 4,
+            (TSpin::None, n) | (TSpin::Mini, n) => n - 1,
+            (TSpin::Full, n) => 2 * n,
+        }
+    }
+
+    /// Tetrises and spins that clear lines keep a back-to-back chain going.
+    fn is_difficult(&self) -> bool {
+        return self.lines >= 4 || (self.lines > 0 && self.scored_tspin() != TSpin::None);
+    }
+
+    fn base_points(&self) -> u64 {
+        match (self.scored_tspin(), self.lines) {
+            (TSpin::None, 0) => 0,
+            (TSpin::None, 1) => 100,
+            (TSpin::None, 2) => 300,
+            (TSpin::None, 3) => 500,
+            (TSpin::None, _) => 800,
+            (TSpin::Mini, 0) => 100,
+            (TSpin::Mini, 1) => 200,
+            (TSpin::Mini, _) => 400,
+            (TSpin::Full, 0) => 400,
+            (TSpin::Full, 1) => 800,
+            (TSpin::Full, 2) => 1200,
+            (TSpin::Full, _) => 1600,
+        }
+    }
+
+    /// Points for leaving the board empty, on top of the clear itself. A
+    /// back-to-back tetris perfect clear earns the most.
+    fn perfect_clear_points(&self, back_to_back: bool) -> u64 {
+        match self.lines {
+            0 => 0,
+            1 => 800,
+            2 => 1200,
+            3 => 1800,
+            _ if back_to_back => 3200,
+            _ => 2000,
+        }
+    }
+}
+
+/// Points, cleared lines and level of a game, scored as in the guideline.
+pub struct Score {
+    points: u64,
+    lines: u32,
+    level: u32,
+    combo: Option<u32>,
+    back_to_back: bool,
+    last_clear: Option<Clear>,
+}
+
+impl Score {
+    fn new() -> Self {
+        Score {
+            points: 0,
+            lines: 0,
+            level: 1,
+            combo: None,
+            back_to_back: false,
+            last_clear: None,
+        }
+    }
+
+    pub fn points(&self) -> u64 {
+        return self.points;
+    }
+
+    pub fn lines(&self) -> u32 {
+        return self.lines;
+    }
+
+    pub fn level(&self) -> u32 {
+        return self.level;
+    }
+
+    /// How many locks in a row have cleared lines, minus one. `None` when
+    /// the last lock cleared nothing.
+    pub fn combo(&self) -> Option<u32> {
+        return self.combo;
+    }
+
+    /// Whether the last clear was difficult, so the next difficult one earns
+    /// a bonus.
+    pub fn back_to_back(&self) -> bool {
+        return self.back_to_back;
+    }
+
+    /// The last lock, if it cleared lines or was a spin.
+    pub fn last_clear(&self) -> Option<Clear> {
+        return self.last_clear;
+    }
+
+    fn soft_drop(&mut self, cells: u32) {
+        self.points += cells as u64;
+    }
+
+    fn hard_drop(&mut self, cells: u32) {
+        self.points += 2 * cells as u64;
+    }
+
+    /// Scores a lock and returns the garbage it sends in versus.
+    fn lock(&mut self, clear: Clear) -> u32 {
+        let lines = clear.lines;
+        let level = self.level as u64;
+        if lines == 0 {
+            self.combo = None;
+            self.points += clear.base_points() * level;
+            self.last_clear = if clear.scored_tspin() == TSpin::None {
+                None
+            } else {
+                Some(clear)
+            };
+            return 0;
+        }
+        let mut points = clear.base_points() * level;
+        let mut garbage = clear.base_garbage();
+        let difficult = clear.is_difficult();
+        if difficult && self.back_to_back {
+            points = points * 3 / 2;
+            garbage += 1;
+        }
+        if clear.perfect {
+            points += clear.perfect_clear_points(difficult && self.back_to_back) * level;
+            garbage += PERFECT_CLEAR_GARBAGE;
+        }
+        self.back_to_back = difficult;
+        self.last_clear = Some(clear);
+        let combo = match self.combo {
+            Some(c) => c + 1,
+            None => 0,
+        };
+        points += 50 * combo as u64 * level;
+        garbage += COMBO_GARBAGE[(combo as usize).min(COMBO_GARBAGE.len() - 1)];
+        self.combo = Some(combo);
+        self.points += points;
+        self.lines += lines;
+        self.level = 1 + self.lines / 10;
+        return garbage;
+    }
+}
+
+fn can_spawn(mino: &Mino, board: &Board) -> bool {
+    return mino.fits(board);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Block {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Block {
+    fn new(x: i32, y: i32) -> Self {
+        Block { x: x, y: y }
+    }
+
+    fn rotate(&self, rot: i32) -> Block {
+        let mut x = self.x;
+        let mut y = self.y;
+        for _ in (0..rot).into_iter() {
+            let tmp = x;
+            x = y;
+            y = -tmp;
+        }
+        return Block::new(x, y);
+    }
+
+    fn shift(&self, shift_x: i32, shift_y: i32) -> Block {
+        return Block::new(self.x + shift_x, self.y + shift_y);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    T,
+    Z,
+    S,
+    L,
+    J,
+    O,
+    I,
+}
+
+pub const SHAPES: [Shape; 7] = [
+    Shape::T,
+    Shape::I,
+    Shape::J,
+    Shape::L,
+    Shape::S,
+    Shape::Z,
+    Shape::O,
+];
+
+impl Shape {
+    /// The letter naming the shape, e.g. `T`.
+    pub fn letter(&self) -> char {
+        match self {
+            Shape::T => 'T',
+            Shape::Z => 'Z',
+            Shape::S => 'S',
+            Shape::L => 'L',
+            Shape::J => 'J',
+            Shape::O => 'O',
+            Shape::I => 'I',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Shape> {
+        return SHAPES.iter().copied().find(|s| s.letter() == letter);
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Rotation {
+    Cw,
+    Ccw,
+    Half,
+}
+
+impl Rotation {
+    fn delta(&self) -> i32 {
+        match self {
+            Rotation::Cw => 1,
+            Rotation::Ccw => -1,
+            Rotation::Half => 2,
+        }
+    }
+}
+
+const JLSTZ_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 0 -> R
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 0
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 2
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 2 -> R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 2 -> L
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 2
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 0
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 0 -> L
+];
+
+const I_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // 0 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // R -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // R -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // 2 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // 2 -> L
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // L -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // L -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // 0 -> L
+];
+
+/// SRS has no 180 degree rotation, so these follow the common SRS+ table,
+/// indexed by the state rotated from.
+const HALF_KICKS: [[(i32, i32); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)], // 0 -> 2
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],   // R -> L
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)], // 2 -> 0
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)], // L -> R
+];
+
+/// Offsets tried in order when rotating from `from` to `to`, where the
+/// rotation states are 0 (spawn), 1 (R), 2 and 3 (L).
+fn wall_kicks(shape: Shape, from: i32, to: i32) -> &'static [(i32, i32)] {
+    if let Shape::O = shape {
+        return &[(0, 0)];
+    }
+    if (to - from).rem_euclid(4) == 2 {
+        return &HALF_KICKS[from as usize];
+    }
+    let index = match (from, to) {
+        (0, 1) => 0,
+        (1, 0) => 1,
+        (1, 2) => 2,
+        (2, 1) => 3,
+        (2, 3) => 4,
+        (3, 2) => 5,
+        (3, 0) => 6,
+        _ => 7,
+    };
+    match shape {
+        Shape::I => &I_KICKS[index],
+        _ => &JLSTZ_KICKS[index],
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Mino {
+    x: i32,
+    y: i32,
+    rot: i32,
+    shape: Shape,
+    /// Index of the wall kick used if the last successful action was a
+    /// rotation, `None` if it was a move.
+    last_kick: Option<usize>,
+    /// Whether that rotation was a 180, whose kicks are not SRS ones.
+    half_turn: bool,
+}
+
+impl Mino {
+    fn new(x: i32, y: i32, rot: i32, shape: Shape) -> Self {
+        Mino {
+            x: x,
+            y: y,
+            rot: rot,
+            shape: shape,
+            last_kick: None,
+            half_turn: false,
+        }
+    }
+
+    pub fn shape(&self) -> Shape {
+        return self.shape;
+    }
+
+    /// The center the blocks are laid out around.
+    pub fn position(&self) -> (i32, i32) {
+        return (self.x, self.y);
+    }
+
+    /// The rotation state: 0 (spawn), 1 (R), 2 or 3 (L).
+    pub fn rotation(&self) -> i32 {
+        return self.rot.rem_euclid(4);
+    }
+
+    /// Where `input` takes this mino on `board`, or `None` if it cannot
+    /// move. Hard drop gives the landing position; hold never moves it.
+    pub fn moved(&self, input: Input, board: &Board) -> Option<Mino> {
+        let mut mino = *self;
+        let moved = match input {
+            Input::MoveLeft => mino.move_left_if_possible(board),
+            Input::MoveRight => mino.move_right_if_possible(board),
+            Input::SoftDrop => mino.move_down_if_possible(board),
+            Input::HardDrop => return Some(self.landing(board)),
+            Input::RotateCw => mino.rotate_if_possible(board, Rotation::Cw),
+            Input::RotateCcw => mino.rotate_if_possible(board, Rotation::Ccw),
+            Input::Rotate180 => mino.rotate_if_possible(board, Rotation::Half),
+            Input::Hold => false,
+        };
+        if !moved {
+            return None;
+        }
+        return Some(mino);
+    }
+
+    /// Whether the mino rests on the stack or the floor.
+    pub fn is_grounded(&self, board: &Board) -> bool {
+        return !self.can_move_down(board);
+    }
+
+    pub fn get_blocks(&self) -> Vec<Block> {
+        let rot = self.rot.rem_euclid(4);
+        let blocks = match self.shape {
+            Shape::T => vec![
+                Block::new(-1, 0),
+                Block::new(0, 0),
+                Block::new(1, 0),
+                Block::new(0, 1),
+            ],
+            Shape::S => vec![
+                Block::new(-1, 0),
+                Block::new(0, 0),
+                Block::new(0, 1),
+                Block::new(1, 1),
+            ],
+            Shape::Z => vec![
+                Block::new(-1, 1),
+                Block::new(0, 1),
+                Block::new(0, 0),
+                Block::new(1, 0),
+            ],
+            Shape::J => vec![
+                Block::new(-1, 1),
+                Block::new(-1, 0),
+                Block::new(0, 0),
+                Block::new(1, 0),
+            ],
+            Shape::L => vec![
+                Block::new(1, 1),
+                Block::new(-1, 0),
+                Block::new(0, 0),
+                Block::new(1, 0),
+            ],
+            // O and I rotate around the corner between cells, so their states
+            // are listed explicitly instead of being rotated around (0, 0).
+            Shape::O => {
+                let cells = [(0, 0), (1, 0), (0, 1), (1, 1)];
+                return cells
+                    .iter()
+                    .map(|&(x, y)| Block::new(x + self.x, y + self.y))
+                    .collect();
+            }
+            Shape::I => {
+                let cells = match rot {
+                    0 => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+                    1 => [(1, 1), (1, 0), (1, -1), (1, -2)],
+                    2 => [(-1, -1), (0, -1), (1, -1), (2, -1)],
+                    _ => [(0, 1), (0, 0), (0, -1), (0, -2)],
+                };
+                return cells
+                    .iter()
+                    .map(|&(x, y)| Block::new(x + self.x, y + self.y))
+                    .collect();
+            }
+        };
+        let ret: Vec<Block> = blocks
+            .iter()
+            .map(|b| b.rotate(rot))
+            .map(|b| b.shift(self.x, self.y))
+            .collect();
+        return ret;
+    }
+
+    fn fits(&self, board: &Board) -> bool {
+        return self
+            .get_blocks()
+            .iter()
+            .all(|b| board.is_blank_at(b.x, b.y));
+    }
+
+    fn can_move_left(&self, board: &Board) -> bool {
+        let mino: Mino = Mino::new(self.x - 1, self.y, self.rot, self.shape);
+        return mino.fits(board);
+    }
+
+    fn can_move_right(&self, board: &Board) -> bool {
+        let mino: Mino = Mino::new(self.x + 1, self.y, self.rot, self.shape);
+        return mino.fits(board);
+    }
+
+    fn can_move_up(&self, board: &Board) -> bool {
+        let mino: Mino = Mino::new(self.x, self.y + 1, self.rot, self.shape);
+        return mino.fits(board);
+    }
+
+    fn can_move_down(&self, board: &Board) -> bool {
+        let mino: Mino = Mino::new(self.x, self.y - 1, self.rot, self.shape);
+        return mino.fits(board);
+    }
+
+    /// Returns the first position the mino can rotate into, trying the SRS
+    /// wall kicks in order.
+    fn kicked_rotation(&self, board: &Board, rotation: Rotation) -> Option<Mino> {
+        let from = self.rot.rem_euclid(4);
+        let to = (self.rot + rotation.delta()).rem_euclid(4);
+        for (i, &(dx, dy)) in wall_kicks(self.shape, from, to).iter().enumerate() {
+            let mut mino = Mino::new(self.x + dx, self.y + dy, to, self.shape);
+            if mino.fits(board) {
+                mino.last_kick = Some(i);
+                if let Rotation::Half = rotation {
+                    mino.half_turn = true;
+                }
+                return Some(mino);
+            }
+        }
+        return None;
+    }
+
+    fn landing(&self, board: &Board) -> Mino {
+        let mut mino = *self;
+        while mino.can_move_down(board) {
+            mino.y -= 1;
+            mino.last_kick = None;
+        }
+        return mino;
+    }
+
+    /// Classifies a lock of this mino with the 3-corner rule: a T rotated
+    /// into place with at least three of the cells diagonal to its center
+    /// occupied is a T-spin, and a mini unless both corners it points at
+    /// are occupied or it got there with the last kick of a 90 degree
+    /// rotation.
+    fn tspin(&self, board: &Board) -> TSpin {
+        let kick = match (self.shape, self.last_kick) {
+            (Shape::T, Some(kick)) => kick,
+            _ => return TSpin::None,
+        };
+        let filled = |dx: i32, dy: i32| !board.is_blank_at(self.x + dx, self.y + dy);
+        // Corners in clockwise order from the top left, so the two corners
+        // in front of rotation state `rot` start at index `rot`.
+        let corners = [filled(-1, 1), filled(1, 1), filled(1, -1), filled(-1, -1)];
+        if corners.iter().filter(|&&c| c).count() < 3 {
+            return TSpin::None;
+        }
+        let rot = self.rot.rem_euclid(4) as usize;
+        if (corners[rot] && corners[(rot + 1) % 4]) || (kick == 4 && !self.half_turn) {
+            return TSpin::Full;
+        }
+        return TSpin::Mini;
+    }
+
+    /// The shape of a mino other than T or O rotated into a spot it cannot
+    /// move out of sideways or up, which all-spin rules count as a spin. An O
+    /// turns in place, so it never spins.
+    fn spin(&self, board: &Board) -> Option<Shape> {
+        if self.shape == Shape::T || self.shape == Shape::O || self.last_kick.is_none() {
+            return None;
+        }
+        if self.can_move_left(board) || self.can_move_right(board) || self.can_move_up(board) {
+            return None;
+        }
+        return Some(self.shape);
+    }
+
+    fn move_left_if_possible(&mut self, board: &Board) -> bool {
+        if self.can_move_left(board) {
+            self.x -= 1;
+            self.last_kick = None;
+            return true;
+        }
+        return false;
+    }
+
+    fn move_right_if_possible(&mut self, board: &Board) -> bool {
+        if self.can_move_right(board) {
+            self.x += 1;
+            self.last_kick = None;
+            return true;
+        }
+        return false;
+    }
+
+    fn move_down_if_possible(&mut self, board: &Board) -> bool {
+        if self.can_move_down(board) {
+            self.y -= 1;
+            self.last_kick = None;
+            return true;
+        }
+        return false;
+    }
+
+    fn rotate_if_possible(&mut self, board: &Board, rotation: Rotation) -> bool {
+        if let Some(mino) = self.kicked_rotation(board, rotation) {
+            *self = mino;
+            return true;
+        }
+        return false;
+    }
+}
+
+/// A cell of the board, remembering which shape filled it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Cell {
+    Empty,
+    Filled(Shape),
+    /// Part of a garbage row sent by an opponent.
+    Garbage,
+}
+
+impl Cell {
+    /// `.` for an empty cell, `#` for garbage and the shape letter otherwise.
+    pub fn to_char(&self) -> char {
+        match self {
+            Cell::Empty => '.',
+            Cell::Garbage => '#',
+            Cell::Filled(shape) => shape.letter(),
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Cell> {
+        match c {
+            '.' => Some(Cell::Empty),
+            '#' => Some(Cell::Garbage),
+            _ => Shape::from_letter(c).map(Cell::Filled),
+        }
+    }
+}
+
+/// The stack of locked cells, indexed by row from the bottom.
+#[derive(Clone)]
+pub struct Board {
+    dimensions: Dimensions,
+    blocks: Vec<Vec<Cell>>,
+}
+
+impl Board {
+    fn new(dimensions: Dimensions) -> Self {
+        let blocks = vec![vec![Cell::Empty; dimensions.width]; dimensions.total_height()];
+        Board {
+            dimensions: dimensions,
+            blocks: blocks,
+        }
+    }
+
+    /// Reads a board of `dimensions` drawn as text, top row first, one
+    /// character per cell as in `Cell::from_char`, e.g.
+    ///
+    /// ```text
+    /// ....T.....
+    /// ...TTT.###
+    /// ##########
+    /// ```
+    ///
+    /// The rows given sit at the bottom of the board, and the rows above them
+    /// are empty. Blank lines before and after the rows are ignored.
+    pub fn from_text(dimensions: Dimensions, text: &str) -> Result<Board, String> {
+        let lines: Vec<&str> = text.trim().lines().map(|l| l.trim()).collect();
+        if lines.len() > dimensions.total_height() {
+            return Err(format!(
+                "{} rows do not fit a board {} rows tall",
+                lines.len(),
+                dimensions.total_height()
+            ));
+        }
+        let mut board = Board::new(dimensions);
+        for (y, line) in lines.iter().rev().enumerate() {
+            if line.chars().count() != dimensions.width {
+                return Err(format!(
+                    "row `{}` is not {} cells wide",
+                    line, dimensions.width
+                ));
+            }
+            for (x, c) in line.chars().enumerate() {
+                board.blocks[y][x] = Cell::from_char(c)
+                    .ok_or_else(|| format!("invalid cell `{}` in row `{}`", c, line))?;
+            }
+        }
+        return Ok(board);
+    }
+
+    /// The board drawn as text for `from_text`, from its highest filled row
+    /// down. An empty board is an empty string.
+    pub fn to_text(&self) -> String {
+        let rows = self
+            .blocks
+            .iter()
+            .rposition(|row| row.iter().any(|&c| c != Cell::Empty))
+            .map_or(0, |y| y + 1);
+        let mut text = String::new();
+        for row in self.blocks[..rows].iter().rev() {
+            text.extend(row.iter().map(|c| c.to_char()));
+            text.push('\n');
+        }
+        return text;
+    }
+
+    /// A board holding `rows`, from the bottom up. Rows and cells beyond
+    /// `dimensions` are dropped, and missing ones are empty.
+    pub fn from_rows(dimensions: Dimensions, rows: &[Vec<Cell>]) -> Self {
+        let mut board = Board::new(dimensions);
+        for (board_row, row) in board.blocks.iter_mut().zip(rows.iter()) {
+            for (cell, &new) in board_row.iter_mut().zip(row.iter()) {
+                *cell = new;
+            }
+        }
+        return board;
+    }
+
+    pub fn dimensions(&self) -> Dimensions {
+        return self.dimensions;
+    }
+
+    pub fn width(&self) -> usize {
+        return self.dimensions.width;
+    }
+
+    /// Number of visible rows.
+    pub fn height(&self) -> usize {
+        return self.dimensions.height;
+    }
+
+    /// The cell at `(x, y)`, or `None` outside the board.
+    pub fn cell_at(&self, x: i32, y: i32) -> Option<Cell> {
+        if x < 0 || x > self.width() as i32 - 1 {
+            return None;
+        }
+        if y < 0 || y > self.blocks.len() as i32 - 1 {
+            return None;
+        }
+        return Some(self.blocks[y as usize][x as usize]);
+    }
+
+    pub fn is_blank_at(&self, x: i32, y: i32) -> bool {
+        return self.cell_at(x, y) == Some(Cell::Empty);
+    }
+
+    /// Whether no cell is filled, as after a perfect clear.
+    pub fn is_empty(&self) -> bool {
+        return self.is_empty_without(&[]);
+    }
+
+    /// Whether no cell is filled outside `rows`, so clearing them empties
+    /// the board.
+    fn is_empty_without(&self, rows: &[usize]) -> bool {
+        return self
+            .blocks
+            .iter()
+            .enumerate()
+            .filter(|(y, _)| !rows.contains(y))
+            .all(|(_, row)| row.iter().all(|&c| c == Cell::Empty));
+    }
+
+    /// Where minos spawn: in the first hidden row, left of center when the
+    /// board has an even width.
+    pub fn spawn_position(&self) -> (i32, i32) {
+        return ((self.width() as i32 - 1) / 2, self.height() as i32);
+    }
+
+    fn spawn_mino(&self, shape: Shape) -> Mino {
+        let (x, y) = self.spawn_position();
+        return Mino::new(x, y, 0, shape);
+    }
+
+    /// Locks `mino` where it is and clears the rows it fills, returning
+    /// their indices. For trying placements out on a copy of the board.
+    pub fn place(&mut self, mino: &Mino) -> Vec<usize> {
+        self.put_mino(mino);
+        return self.delete_lines();
+    }
+
+    fn put_mino(&mut self, mino: &Mino) {
+        for b in mino.get_blocks() {
+            self.put_block(&b, mino.shape);
+        }
+    }
+
+    fn put_block(&mut self, block: &Block, shape: Shape) {
+        let x = block.x as usize;
+        let y = block.y as usize;
+        self.blocks[y][x] = Cell::Filled(shape);
+    }
+
+    /// Pushes the stack up by `lines` rows and fills them in from the bottom
+    /// with garbage, leaving column `hole` empty. Returns false if blocks
+    /// were pushed out of the top.
+    pub fn insert_garbage(&mut self, lines: usize, hole: usize) -> bool {
+        let height = self.blocks.len();
+        let lines = lines.min(height);
+        let mut row = vec![Cell::Garbage; self.width()];
+        row[hole] = Cell::Empty;
+        let pushed_out = self.blocks[height - lines..]
+            .iter()
+            .any(|r| r.iter().any(|&c| c != Cell::Empty));
+        self.blocks.truncate(height - lines);
+        for _ in (0..lines).into_iter() {
+            self.blocks.insert(0, row.clone());
+        }
+        return !pushed_out;
+    }
+
+    /// Indices of the full rows, from the bottom up.
+    fn full_rows(&self) -> Vec<usize> {
+        return (0..self.blocks.len())
+            .into_iter()
+            .filter(|&y| self.blocks[y].iter().all(|&c| c != Cell::Empty))
+            .collect();
+    }
+
+    /// Removes every full row, moving the rows above down over them, and
+    /// returns the indices the cleared rows had, from the bottom up.
+    fn delete_lines(&mut self) -> Vec<usize> {
+        let height = self.blocks.len();
+        let width = self.width();
+        let cleared = self.full_rows();
+        self.blocks
+            .retain(|row| row.iter().any(|&c| c == Cell::Empty));
+        self.blocks.resize(height, vec![Cell::Empty; width]);
+        return cleared;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const CASES: u64 = 500;
+
+    /// A board with `rows` filled in from the bottom, as in
+    /// `Board::from_text`.
+    fn board_from_rows(dimensions: Dimensions, rows: &[&str]) -> Board {
+        let text: Vec<&str> = rows.iter().rev().copied().collect();
+        return Board::from_text(dimensions, &text.join("\n")).unwrap();
+    }
+
+    /// A board of random size whose rows are each full, empty or partly
+    /// filled, so adjacent and separated full rows both come up often.
+    fn random_board(rng: &mut StdRng) -> Board {
+        let dimensions = Dimensions {
+            width: rng.gen_range(MIN_BOARD_WIDTH..=12),
+            height: rng.gen_range(MIN_BOARD_HEIGHT..=24),
+            hidden_rows: rng.gen_range(MIN_HIDDEN_ROWS..=4),
+        };
+        let mut board = Board::new(dimensions);
+        for row in board.blocks.iter_mut() {
+            let fill = match rng.gen_range(0..4) {
+                0 => 0.0,
+                1 => 1.0,
+                _ => rng.gen_range(0.0..1.0),
+            };
+            for cell in row.iter_mut() {
+                if rng.gen_bool(fill) {
+                    *cell = Cell::Filled(SHAPES[rng.gen_range(0..SHAPES.len())]);
+                }
+            }
+        }
+        return board;
+    }
+
+    fn is_full(row: &[Cell]) -> bool {
+        return row.iter().all(|&c| c != Cell::Empty);
+    }
+
+    #[test]
+    fn delete_lines_clears_adjacent_full_rows() {
+        let dimensions = Dimensions {
+            width: 4,
+            height: 6,
+            hidden_rows: 2,
+        };
+        let mut board = board_from_rows(dimensions, &["####", "####", "#..#", "####", ".#.."]);
+        assert_eq!(board.delete_lines(), vec![0, 1, 3]);
+        let expected = board_from_rows(dimensions, &["#..#", ".#.."]);
+        assert_eq!(board.blocks, expected.blocks);
+    }
+
+    #[test]
+    fn delete_lines_clears_top_row() {
+        let dimensions = Dimensions {
+            width: 4,
+            height: 2,
+            hidden_rows: 2,
+        };
+        let mut board = board_from_rows(dimensions, &["#...", "....", "####", "####"]);
+        assert_eq!(board.delete_lines(), vec![2, 3]);
+        let expected = board_from_rows(dimensions, &["#..."]);
+        assert_eq!(board.blocks, expected.blocks);
+    }
+
+    #[test]
+    fn delete_lines_returns_exactly_the_full_rows() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in (0..CASES).into_iter() {
+            let mut board = random_board(&mut rng);
+            let full: Vec<usize> = (0..board.blocks.len())
+                .into_iter()
+                .filter(|&y| is_full(&board.blocks[y]))
+                .collect();
+            assert_eq!(board.delete_lines(), full);
+        }
+    }
+
+    #[test]
+    fn delete_lines_leaves_no_full_row() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in (0..CASES).into_iter() {
+            let mut board = random_board(&mut rng);
+            board.delete_lines();
+            assert!(board.blocks.iter().all(|row| !is_full(row)));
+        }
+    }
+
+    #[test]
+    fn delete_lines_keeps_dimensions() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in (0..CASES).into_iter() {
+            let mut board = random_board(&mut rng);
+            let dimensions = board.dimensions();
+            board.delete_lines();
+            assert_eq!(board.blocks.len(), dimensions.total_height());
+            assert!(board.blocks.iter().all(|row| row.len() == dimensions.width));
+        }
+    }
+
+    #[test]
+    fn delete_lines_keeps_other_rows_in_order() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in (0..CASES).into_iter() {
+            let mut board = random_board(&mut rng);
+            let kept: Vec<Vec<Cell>> = board
+                .blocks
+                .iter()
+                .filter(|row| !is_full(row))
+                .cloned()
+                .collect();
+            let cleared = board.delete_lines();
+            assert_eq!(&board.blocks[..kept.len()], &kept[..]);
+            let empty = vec![Cell::Empty; board.width()];
+            assert!(board.blocks[kept.len()..].iter().all(|row| *row == empty));
+            assert_eq!(kept.len() + cleared.len(), board.blocks.len());
+        }
+    }
+
+    #[test]
+    fn delete_lines_clears_a_drawn_tetris() {
+        let dimensions = Dimensions::default();
+        let mut board = Board::from_text(
+            dimensions,
+            "
+            ....L.....
+            ..LLL....I
+            #########I
+            #########I
+            #########I
+            ###.######
+            ",
+        )
+        .unwrap();
+        assert_eq!(board.delete_lines(), vec![1, 2, 3]);
+        assert_eq!(board.to_text(), "....L.....\n..LLL....I\n###.######\n");
+    }
+
+    #[test]
+    fn can_spawn_only_while_the_spawn_row_is_free() {
+        let dimensions = Dimensions {
+            width: 10,
+            height: 4,
+            hidden_rows: 2,
+        };
+        let below =
+            Board::from_text(dimensions, "....#.....\n.########.\n##########\n.#########").unwrap();
+        let blocked = Board::from_text(
+            dimensions,
+            "....#.....\n....#.....\n.########.\n##########\n.#########",
+        )
+        .unwrap();
+        for &shape in SHAPES.iter() {
+            assert!(can_spawn(&below.spawn_mino(shape), &below));
+            assert!(!can_spawn(&blocked.spawn_mino(shape), &blocked));
+        }
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in (0..CASES).into_iter() {
+            let board = random_board(&mut rng);
+            let read = Board::from_text(board.dimensions(), &board.to_text()).unwrap();
+            assert_eq!(read.blocks, board.blocks);
+        }
+    }
+
+    #[test]
+    fn from_text_rejects_bad_boards() {
+        let dimensions = Dimensions {
+            width: 4,
+            height: 2,
+            hidden_rows: 2,
+        };
+        assert!(Board::from_text(dimensions, "....\n...").is_err());
+        assert!(Board::from_text(dimensions, "..x.").is_err());
+        assert!(Board::from_text(dimensions, "#...\n#...\n#...\n#...\n#...").is_err());
+    }
+
+    #[test]
+    fn t_kicks_off_the_left_wall() {
+        let board = Board::new(Dimensions::default());
+        let mino = Mino::new(0, 5, 1, Shape::T);
+        let rotated = mino.moved(Input::RotateCw, &board).unwrap();
+        assert_eq!(rotated.position(), (1, 5));
+        assert_eq!(rotated.rotation(), 2);
+        assert_eq!(rotated.last_kick, Some(1));
+    }
+
+    #[test]
+    fn i_kicks_off_the_left_wall() {
+        let board = Board::new(Dimensions::default());
+        let mino = Mino::new(-1, 5, 1, Shape::I);
+        let rotated = mino.moved(Input::RotateCw, &board).unwrap();
+        assert_eq!(rotated.position(), (1, 5));
+        assert_eq!(rotated.rotation(), 2);
+        assert_eq!(rotated.last_kick, Some(2));
+    }
+
+    #[test]
+    fn t_kicks_down_into_a_triple_slot() {
+        let text = "\
+            ...#......\n\
+            ..........\n\
+            ###.######\n\
+            ###..#####\n\
+            ###.######";
+        let mut board = Board::from_text(Dimensions::default(), text).unwrap();
+        let mino = Mino::new(4, 3, 0, Shape::T);
+        let rotated = mino.moved(Input::RotateCw, &board).unwrap();
+        assert_eq!(rotated.position(), (3, 1));
+        assert_eq!(rotated.last_kick, Some(4));
+        assert_eq!(rotated.tspin(&board), TSpin::Full);
+        assert_eq!(board.place(&rotated).len(), 3);
+    }
+
+    /// Steps `game` in frames of 10ms until `done` holds, failing after a
+    /// minute of game time.
+    fn step_until<F: Fn(&Game) -> bool>(game: &mut Game, done: F) {
+        for _ in (0..6000).into_iter() {
+            if done(game) {
+                return;
+            }
+            game.step(Duration::from_millis(10));
+        }
+        panic!("game never reached the expected state");
+    }
+
+    #[test]
+    fn minos_spawn_at_the_top_center() {
+        let mut game = Game::with_seed(RandomizerKind::Bag7, 3);
+        let spawn = game.board().spawn_position();
+        assert_eq!(spawn, (4, 20));
+        assert_eq!(game.mino().position(), spawn);
+        assert_eq!(game.mino().rotation(), 0);
+        game.apply(Input::HardDrop);
+        step_until(&mut game, |g| g.is_falling());
+        assert_eq!(game.pieces(), 1);
+        assert_eq!(game.mino().position(), spawn);
+    }
+
+    #[test]
+    fn games_start_on_a_loaded_board() {
+        let board = Board::from_text(Dimensions::default(), "L.........\nLLLL..JJJJ").unwrap();
+        let game = Game::with_board(board.clone(), RandomizerKind::Bag7, 3);
+        assert!(game.is_active());
+        assert_eq!(game.board().to_text(), board.to_text());
+        let full = vec!["LLLL..JJJJ"; 22].join("\n");
+        let board = Board::from_text(Dimensions::default(), &full).unwrap();
+        assert!(!Game::with_board(board, RandomizerKind::Bag7, 3).is_active());
+    }
+
+    #[test]
+    fn gravity_locks_and_clears_lines_after_the_delay() {
+        let board = Board::from_text(Dimensions::default(), "####..####\n####..####").unwrap();
+        let mut game = Game::with_sequence(board, vec![Shape::O]);
+        step_until(&mut game, |g| g.pieces() == 1);
+        match game.phase() {
+            Phase::LineClear { rows, .. } => assert_eq!(rows, &vec![0, 1]),
+            phase => panic!("expected a line clear, not {:?}", phase),
+        }
+        // The full rows stay until the line-clear delay is over.
+        assert!(!game.board().is_empty());
+        assert_eq!(game.score().lines(), 2);
+        step_until(&mut game, |g| g.is_falling());
+        assert!(game.board().is_empty());
+        assert!(game.is_active());
+    }
+
+    #[test]
+    fn game_ends_when_a_mino_cannot_spawn() {
+        let mut game = Game::with_sequence(Board::new(Dimensions::default()), vec![Shape::O]);
+        game.set_delays(Delays::none());
+        for _ in (0..20).into_iter() {
+            game.apply(Input::HardDrop);
+        }
+        assert!(!game.is_active());
+        // The eleventh O locks in the spawn rows, so the twelfth cannot
+        // spawn.
+        assert_eq!(game.pieces(), 11);
+        let board = game.board().to_text();
+        game.apply(Input::HardDrop);
+        game.step(Duration::from_secs(1));
+        assert_eq!(game.pieces(), 11);
+        assert_eq!(game.board().to_text(), board);
+    }
+
+    #[test]
+    fn last_180_kick_does_not_make_a_full_t_spin() {
+        let text = "\
+            ...#.#....\n\
+            ..........\n\
+            .....#....\n\
+            ...#......\n\
+            ..........";
+        let board = Board::from_text(Dimensions::default(), text).unwrap();
+        let mino = Mino::new(4, 1, 1, Shape::T);
+        let rotated = mino.moved(Input::Rotate180, &board).unwrap();
+        assert_eq!(rotated.position(), (4, 3));
+        assert_eq!(rotated.last_kick, Some(4));
+        assert_eq!(rotated.tspin(&board), TSpin::Mini);
+    }
+
+    /// A game on `board` dealing `shapes` over and over without delays,
+    /// after playing `inputs`.
+    pub(crate) fn play(board: &str, shapes: &[Shape], all_spin: bool, inputs: &[Input]) -> Game {
+        let board = Board::from_text(Dimensions::default(), board).unwrap();
+        let mut game = Game::with_sequence(board, shapes.to_vec());
+        game.set_delays(Delays::none());
+        game.set_all_spin(all_spin);
+        for &input in inputs.iter() {
+            game.apply(input);
+        }
+        return game;
+    }
+
+    #[test]
+    fn garbage_rises_on_a_lock_that_clears_nothing() {
+        let mut game = play("", &[Shape::O], false, &[]);
+        game.receive_garbage(2);
+        game.receive_garbage(1);
+        assert_eq!(game.pending_garbage(), 3);
+        game.apply(Input::HardDrop);
+        assert_eq!(game.pending_garbage(), 0);
+        let text = game.board().to_text();
+        let rows: Vec<&str> = text.lines().rev().collect();
+        assert_eq!(rows.len(), 5);
+        for row in rows[..3].iter() {
+            assert_eq!(row.matches('#').count(), 9);
+        }
+        // The oldest rows rise first, and rows sent together share a hole.
+        assert_eq!(rows[1], rows[2]);
+        assert_eq!(rows[3], "....OO....");
+    }
+
+    #[test]
+    fn garbage_waits_while_locks_clear_lines() {
+        let mut game = play("L.........\nLLLL..JJJJ", &[Shape::O], false, &[]);
+        game.receive_garbage(2);
+        game.apply(Input::HardDrop);
+        // A single sends nothing, so nothing is cancelled either.
+        assert_eq!(game.score().lines(), 1);
+        assert_eq!(game.take_attack(), 0);
+        assert_eq!(game.pending_garbage(), 2);
+        assert!(!game.board().to_text().contains('#'));
+        game.apply(Input::HardDrop);
+        assert_eq!(game.pending_garbage(), 0);
+        assert!(game.board().to_text().contains('#'));
+    }
+
+    #[test]
+    fn clears_cancel_queued_garbage_before_sending() {
+        // A double sends 1, cancelling one of the 3 queued rows.
+        let mut game = play(
+            "#.........\n####..####\n####..####",
+            &[Shape::O],
+            false,
+            &[],
+        );
+        game.receive_garbage(3);
+        game.apply(Input::HardDrop);
+        assert_eq!(game.pending_garbage(), 2);
+        assert_eq!(game.take_attack(), 0);
+        assert_eq!(game.sent(), 0);
+
+        // A perfect clear double sends 11: 3 cancel the queue, 8 go out.
+        let mut game = play("####..####\n####..####", &[Shape::O], false, &[]);
+        game.receive_garbage(3);
+        game.apply(Input::HardDrop);
+        assert_eq!(game.pending_garbage(), 0);
+        assert_eq!(game.take_attack(), 8);
+        assert_eq!(game.take_attack(), 0);
+        assert_eq!(game.sent(), 8);
+    }
+
+    #[test]
+    fn garbage_seed_picks_the_holes() {
+        let hole_rows = |garbage_seed: Option<u64>| {
+            let mut game = play("", &[Shape::O], false, &[]);
+            if let Some(seed) = garbage_seed {
+                game.set_garbage_seed(seed);
+            }
+            for _ in (0..8).into_iter() {
+                game.receive_garbage(1);
+            }
+            game.apply(Input::HardDrop);
+            return game.board().to_text();
+        };
+        assert_eq!(hole_rows(None), hole_rows(None));
+        assert_eq!(hole_rows(Some(1)), hole_rows(Some(1)));
+        assert_ne!(hole_rows(Some(1)), hole_rows(Some(2)));
+    }
+
+    #[test]
+    fn huge_garbage_fills_the_board_at_most() {
+        let mut game = play("", &[Shape::O], false, &[]);
+        game.receive_garbage(u32::MAX);
+        game.receive_garbage(u32::MAX);
+        assert_eq!(game.pending_garbage(), 48);
+        game.apply(Input::HardDrop);
+        assert!(!game.is_active());
+    }
+
+    #[test]
+    fn delete_lines_is_idempotent() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in (0..CASES).into_iter() {
+            let mut board = random_board(&mut rng);
+            board.delete_lines();
+            let blocks = board.blocks.clone();
+            assert!(board.delete_lines().is_empty());
+            assert_eq!(board.blocks, blocks);
+        }
+    }
+
+    /// Drops the falling piece onto the stack without locking it, then
+    /// applies `inputs`.
+    fn after_landing(inputs: &[Input]) -> Vec<Input> {
+        let mut landing = vec![Input::SoftDrop; 25];
+        landing.extend_from_slice(inputs);
+        return landing;
+    }
+
+    #[test]
+    fn perfect_clear_scores_a_bonus() {
+        let board = "####..####\n####..####";
+        let mut game = play(board, &[Shape::O], false, &[Input::HardDrop]);
+        let clear = game.score().last_clear().unwrap();
+        assert_eq!(clear.lines, 2);
+        assert!(clear.perfect);
+        assert!(game.board().is_empty());
+
+        let mut other = play(
+            &format!("#.........\n{}", board),
+            &[Shape::O],
+            false,
+            &[Input::HardDrop],
+        );
+        assert!(!other.score().last_clear().unwrap().perfect);
+        assert_eq!(game.score().points() - other.score().points(), 1200);
+        assert_eq!(
+            game.take_attack(),
+            other.take_attack() + PERFECT_CLEAR_GARBAGE
+        );
+    }
+
+    #[test]
+    fn all_spin_scores_an_immobile_piece_only_when_enabled() {
+        let board = "#####..###\n####..####";
+        let inputs = after_landing(&[Input::RotateCw, Input::RotateCw, Input::HardDrop]);
+        let spin = play(board, &[Shape::S], true, &inputs);
+        let clear = spin.score().last_clear().unwrap();
+        assert_eq!(clear.spin, Some(Shape::S));
+        assert_eq!(clear.name(), "S-SPIN DOUBLE");
+
+        let plain = play(board, &[Shape::S], false, &inputs);
+        let clear = plain.score().last_clear().unwrap();
+        assert_eq!(clear.spin, None);
+        assert_eq!(clear.name(), "DOUBLE");
+        assert!(spin.score().points() > plain.score().points());
+    }
+
+    #[test]
+    fn all_spin_needs_the_piece_to_be_stuck() {
+        let inputs = after_landing(&[Input::RotateCw, Input::HardDrop]);
+        let game = play("#.........", &[Shape::S], true, &inputs);
+        assert_eq!(game.pieces(), 1);
+        assert_eq!(game.score().last_clear(), None);
+    }
+
+    #[test]
+    fn all_spin_never_counts_an_o() {
+        // An O sealed into a pocket of its own size, turned in place.
+        let mino = Mino::new(4, 5, 0, Shape::O);
+        let mut board = Board::new(Dimensions::default());
+        for row in board.blocks.iter_mut().take(10) {
+            for cell in row.iter_mut() {
+                *cell = Cell::Garbage;
+            }
+        }
+        for b in mino.get_blocks() {
+            board.blocks[b.y as usize][b.x as usize] = Cell::Empty;
+        }
+        let rotated = mino.moved(Input::RotateCw, &board).unwrap();
+        assert_eq!(rotated.last_kick, Some(0));
+        assert_eq!(rotated.spin(&board), None);
+    }
+
+    #[test]
+    fn all_spin_leaves_t_spins_to_the_corner_rule() {
+        let board = "####......\n###...####\n####.#####";
+        let mut inputs = vec![Input::RotateCw];
+        inputs.extend(after_landing(&[Input::RotateCw, Input::HardDrop]));
+        for &all_spin in [false, true].iter() {
+            let game = play(board, &[Shape::T], all_spin, &inputs);
+            let clear = game.score().last_clear().unwrap();
+            assert_eq!(clear.tspin, TSpin::Full);
+            assert_eq!(clear.spin, None);
+            assert_eq!(clear.name(), "T-SPIN DOUBLE");
+        }
+    }
+
+    /// A plain clear of `lines` lines.
+    fn clear(lines: u32) -> Clear {
+        Clear {
+            lines: lines,
+            tspin: TSpin::None,
+            spin: None,
+            perfect: false,
+        }
+    }
+
+    #[test]
+    fn scores_singles_through_tetrises() {
+        for &(lines, points) in [(1, 100), (2, 300), (3, 500), (4, 800)].iter() {
+            let mut score = Score::new();
+            score.lock(clear(lines));
+            assert_eq!(score.points(), points);
+            assert_eq!(score.lines(), lines);
+        }
+    }
+
+    #[test]
+    fn back_to_back_tetrises_score_half_again() {
+        let mut score = Score::new();
+        score.lock(clear(4));
+        assert!(score.back_to_back());
+        // A lock clearing nothing ends the combo but not the chain.
+        score.lock(clear(0));
+        score.lock(clear(4));
+        assert_eq!(score.points(), 800 + 1200);
+        score.lock(clear(0));
+        score.lock(clear(1));
+        assert!(!score.back_to_back());
+        score.lock(clear(0));
+        score.lock(clear(4));
+        assert_eq!(score.points(), 800 + 1200 + 100 + 800);
+    }
+
+    #[test]
+    fn combos_add_50_per_lock_in_a_row() {
+        let mut score = Score::new();
+        for _ in (0..4).into_iter() {
+            score.lock(clear(1));
+        }
+        assert_eq!(score.combo(), Some(3));
+        assert_eq!(score.points(), 100 + 150 + 200 + 250);
+        score.lock(clear(0));
+        assert_eq!(score.combo(), None);
+        score.lock(clear(1));
+        assert_eq!(score.points(), 700 + 100);
+    }
+
+    #[test]
+    fn levels_rise_every_10_lines() {
+        let mut score = Score::new();
+        for _ in (0..9).into_iter() {
+            score.lock(clear(1));
+            score.lock(clear(0));
+        }
+        assert_eq!(score.level(), 1);
+        score.lock(clear(1));
+        score.lock(clear(0));
+        assert_eq!(score.level(), 2);
+        let points = score.points();
+        score.lock(clear(1));
+        assert_eq!(score.points() - points, 200);
+    }
+
+    #[test]
+    fn gravity_speeds_up_until_level_20() {
+        assert_eq!(gravity_interval(1), Duration::from_secs(1));
+        for level in (1..20).into_iter() {
+            assert!(gravity_interval(level + 1) < gravity_interval(level));
+        }
+        assert_eq!(gravity_interval(25), gravity_interval(20));
+    }
+
+    /// Soft drops the O of `board` as far as it goes, so it rests without
+    /// locking.
+    fn grounded_o(board: &str) -> Game {
+        return play(board, &[Shape::O], false, &[Input::SoftDrop; 25]);
+    }
+
+    /// Applies `input` and then steps `ms` milliseconds.
+    fn input_then_step(game: &mut Game, input: Input, ms: u64) {
+        game.apply(input);
+        game.step(Duration::from_millis(ms));
+    }
+
+    #[test]
+    fn grounded_minos_lock_after_the_lock_delay() {
+        let mut game = grounded_o("");
+        game.step(LOCK_DELAY - Duration::from_millis(1));
+        assert_eq!(game.pieces(), 0);
+        game.step(Duration::from_millis(1));
+        assert_eq!(game.pieces(), 1);
+    }
+
+    #[test]
+    fn moves_and_rotations_reset_the_lock_delay() {
+        let mut game = grounded_o("");
+        for &input in [Input::MoveLeft, Input::RotateCw, Input::MoveRight].iter() {
+            input_then_step(&mut game, input, 400);
+        }
+        assert_eq!(game.pieces(), 0);
+        game.step(Duration::from_millis(100));
+        assert_eq!(game.pieces(), 1);
+    }
+
+    #[test]
+    fn lock_resets_run_out() {
+        let mut game = grounded_o("");
+        for i in (0..MAX_LOCK_RESETS - 1).into_iter() {
+            let input = [Input::MoveLeft, Input::MoveRight][i as usize % 2];
+            input_then_step(&mut game, input, 100);
+        }
+        assert_eq!(game.pieces(), 0);
+        input_then_step(&mut game, Input::MoveLeft, 1);
+        assert_eq!(game.pieces(), 1);
+    }
+
+    #[test]
+    fn reaching_a_lower_row_restores_the_lock_resets() {
+        // The O rests on a pillar, then slides off it to the floor.
+        let mut game = grounded_o("....L.....\n....L.....");
+        for i in (0..MAX_LOCK_RESETS - 2).into_iter() {
+            let input = [Input::MoveLeft, Input::MoveRight][i as usize % 2];
+            input_then_step(&mut game, input, 100);
+        }
+        input_then_step(&mut game, Input::MoveLeft, 0);
+        input_then_step(&mut game, Input::MoveLeft, 0);
+        assert_eq!(game.mino().position().1, game.ghost().y + 2);
+        game.apply(Input::SoftDrop);
+        game.apply(Input::SoftDrop);
+        for i in (0..MAX_LOCK_RESETS - 1).into_iter() {
+            let input = [Input::MoveRight, Input::MoveLeft][i as usize % 2];
+            input_then_step(&mut game, input, 100);
+        }
+        assert_eq!(game.pieces(), 0);
+    }
+}
@@ -0,0 +1,186 @@
+//! Boards as Fumen (v115) strings, the format the common online editors use
+//! to share positions, e.g. `v115@bhI8KeAgH` for a row of garbage with a
+//! hole on the right.
+//!
+//! Only the field of the first page is read or written: later pages, the
+//! falling piece and comments are ignored. Fumen fields are 10 columns wide
+//! and 23 rows tall, above a garbage row that is left out.
+
+use crate::engine::{Board, Cell, Dimensions, Shape};
+
+const PREFIX: &str = "v115@";
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const FIELD_WIDTH: usize = 10;
+const FIELD_HEIGHT: usize = 23;
+/// Cells in a field, counting the garbage row.
+const FIELD_CELLS: usize = FIELD_WIDTH * (FIELD_HEIGHT + 1);
+
+/// The first page's flags with no falling piece: guideline colours on, and
+/// no comment.
+const EMPTY_ACTION: &str = "AgH";
+
+fn cell_value(cell: Cell) -> usize {
+    match cell {
+        Cell::Empty => 0,
+        Cell::Filled(Shape::I) => 1,
+        Cell::Filled(Shape::L) => 2,
+        Cell::Filled(Shape::O) => 3,
+        Cell::Filled(Shape::Z) => 4,
+        Cell::Filled(Shape::T) => 5,
+        Cell::Filled(Shape::J) => 6,
+        Cell::Filled(Shape::S) => 7,
+        Cell::Garbage => 8,
+    }
+}
+
+fn value_cell(value: usize) -> Cell {
+    match value {
+        1 => Cell::Filled(Shape::I),
+        2 => Cell::Filled(Shape::L),
+        3 => Cell::Filled(Shape::O),
+        4 => Cell::Filled(Shape::Z),
+        5 => Cell::Filled(Shape::T),
+        6 => Cell::Filled(Shape::J),
+        7 => Cell::Filled(Shape::S),
+        8 => Cell::Garbage,
+        _ => Cell::Empty,
+    }
+}
+
+/// Encodes `board`, which must be 10 columns wide with nothing above the
+/// 23rd row.
+pub fn encode(board: &Board) -> Result<String, String> {
+    if board.width() != FIELD_WIDTH {
+        return Err(format!(
+            "fumen boards are {} columns wide, not {}",
+            FIELD_WIDTH,
+            board.width()
+        ));
+    }
+    let total_height = board.dimensions().total_height() as i32;
+    if (FIELD_HEIGHT as i32..total_height)
+        .into_iter()
+        .any(|y| (0..FIELD_WIDTH as i32).any(|x| !board.is_blank_at(x, y)))
+    {
+        return Err(format!("fumen boards are {} rows tall", FIELD_HEIGHT));
+    }
+    // Cells from the top left, row by row, with the garbage row left empty.
+    let values: Vec<usize> = (0..FIELD_CELLS)
+        .into_iter()
+        .map(|i| {
+            let x = (i % FIELD_WIDTH) as i32;
+            let y = FIELD_HEIGHT as i32 - 1 - (i / FIELD_WIDTH) as i32;
+            board.cell_at(x, y).map_or(0, cell_value)
+        })
+        .collect();
+    let mut text = PREFIX.to_string();
+    let mut start = 0;
+    while start < values.len() {
+        let run = values[start..]
+            .iter()
+            .take_while(|&&v| v == values[start])
+            .count();
+        // Each run is stored as its difference from the empty field, offset
+        // to be positive, and its length.
+        push_number(&mut text, (values[start] + 8) * FIELD_CELLS + run - 1, 2);
+        if values[start] == 0 && run == FIELD_CELLS {
+            // How many following pages repeat the empty field.
+            push_number(&mut text, 0, 1);
+        }
+        start += run;
+    }
+    text += EMPTY_ACTION;
+    return Ok(text);
+}
+
+/// Decodes the first page of `text` onto a board of the default size.
+pub fn decode(text: &str) -> Result<Board, String> {
+    let data = text
+        .trim()
+        .strip_prefix(PREFIX)
+        .ok_or_else(|| format!("fumen must start with `{}`", PREFIX))?;
+    // Links split long strings with `?`.
+    let digits: Vec<usize> = data
+        .chars()
+        .filter(|&c| c != '?')
+        .map(|c| {
+            ALPHABET
+                .iter()
+                .position(|&a| a as char == c)
+                .ok_or_else(|| format!("invalid fumen character `{}`", c))
+        })
+        .collect::<Result<_, _>>()?;
+    let mut values = vec![];
+    let mut i = 0;
+    while values.len() < FIELD_CELLS {
+        if i + 2 > digits.len() {
+            return Err("fumen field ends early".to_string());
+        }
+        let number = digits[i] + 64 * digits[i + 1];
+        i += 2;
+        let value = (number / FIELD_CELLS).checked_sub(8);
+        let run = number % FIELD_CELLS + 1;
+        match value {
+            Some(value) if value <= 8 && values.len() + run <= FIELD_CELLS => {
+                values.extend(std::iter::repeat(value).take(run));
+            }
+            _ => return Err("invalid fumen field".to_string()),
+        }
+    }
+    let mut rows = vec![vec![Cell::Empty; FIELD_WIDTH]; FIELD_HEIGHT];
+    for (i, &value) in values[..FIELD_WIDTH * FIELD_HEIGHT].iter().enumerate() {
+        rows[FIELD_HEIGHT - 1 - i / FIELD_WIDTH][i % FIELD_WIDTH] = value_cell(value);
+    }
+    return Ok(Board::from_rows(Dimensions::default(), &rows));
+}
+
+/// Appends `number` as `digits` base-64 digits, least significant first.
+fn push_number(text: &mut String, mut number: usize, digits: usize) {
+    for _ in (0..digits).into_iter() {
+        text.push(ALPHABET[number % 64] as char);
+        number /= 64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_board_matches_the_editors() {
+        let board = Board::from_rows(Dimensions::default(), &[]);
+        assert_eq!(encode(&board).unwrap(), "v115@vhAAgH");
+        assert_eq!(decode("v115@vhAAgH").unwrap().to_text(), "");
+    }
+
+    #[test]
+    fn decodes_a_garbage_row() {
+        let board = decode("v115@bhI8KeAgH").unwrap();
+        assert_eq!(board.to_text(), "#########.\n");
+        assert_eq!(encode(&board).unwrap(), "v115@bhI8KeAgH");
+    }
+
+    #[test]
+    fn round_trips_every_cell() {
+        let text = "\
+            TTTSSZZ.OO\n\
+            .T.SSZZ#OO\n\
+            LLLJJJIIII\n\
+            L..J...###\n";
+        let board = Board::from_text(Dimensions::default(), text).unwrap();
+        let decoded = decode(&encode(&board).unwrap()).unwrap();
+        assert_eq!(decoded.to_text(), board.to_text());
+    }
+
+    #[test]
+    fn rejects_what_fumen_cannot_hold() {
+        let narrow = Dimensions {
+            width: 8,
+            ..Dimensions::default()
+        };
+        assert!(encode(&Board::from_text(narrow, "#.......").unwrap()).is_err());
+        assert!(decode("v115@bhI8").is_err());
+        assert!(decode("x115@vhAAgH").is_err());
+    }
+}
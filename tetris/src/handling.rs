@@ -0,0 +1,300 @@
+//! Auto-repeat of held movement keys, so pieces shift at a steady, configured
+//! rate instead of relying on the operating system's key repeat.
+
+use std::time::Duration;
+
+use crate::engine::{Input, MAX_BOARD_WIDTH};
+
+/// How held movement keys repeat.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Handling {
+    /// Delayed Auto Shift: how long a side key is held before it repeats.
+    pub das: Duration,
+    /// Auto Repeat Rate: time between repeated shifts. Zero keeps the mino
+    /// against the wall while held, whatever the width of the board.
+    pub arr: Duration,
+    /// Soft-drop factor: how many times faster than gravity a held soft drop
+    /// moves the mino.
+    pub sdf: u32,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling {
+            das: Duration::from_millis(167),
+            arr: Duration::from_millis(33),
+            sdf: 20,
+        }
+    }
+}
+
+/// A held key and how many inputs it has produced so far.
+struct Repeat {
+    input: Input,
+    elapsed: Duration,
+    count: u32,
+}
+
+impl Repeat {
+    fn new(input: Input) -> Self {
+        Repeat {
+            input: input,
+            elapsed: Duration::from_secs(0),
+            count: 0,
+        }
+    }
+
+    /// Advances by `dt` and returns how many inputs became due, given the
+    /// total number due after `elapsed`.
+    fn advance(&mut self, dt: Duration, due: impl Fn(Duration) -> u32) -> u32 {
+        self.elapsed += dt;
+        let total = due(self.elapsed);
+        let n = total.saturating_sub(self.count);
+        self.count = total.max(self.count);
+        return n;
+    }
+}
+
+pub struct AutoRepeat {
+    handling: Handling,
+    /// Keys held down for each action, as several keys can be bound to one.
+    left_held: u32,
+    right_held: u32,
+    soft_drop_held: u32,
+    shift: Option<Repeat>,
+    /// Time towards the next repeated soft drop while it is held.
+    soft_drop: Option<Duration>,
+}
+
+impl AutoRepeat {
+    pub fn new(handling: Handling) -> Self {
+        AutoRepeat {
+            handling: handling,
+            left_held: 0,
+            right_held: 0,
+            soft_drop_held: 0,
+            shift: None,
+            soft_drop: None,
+        }
+    }
+
+    /// Starts repeating `input` and returns the input to apply right away,
+    /// if any. Inputs that do not repeat are returned as they are. The most
+    /// recently pressed side wins while both are held, and pressing a second
+    /// key for an action already held does nothing.
+    pub fn press(&mut self, input: Input) -> Option<Input> {
+        let held = match input {
+            Input::MoveLeft => &mut self.left_held,
+            Input::MoveRight => &mut self.right_held,
+            Input::SoftDrop => &mut self.soft_drop_held,
+            _ => return Some(input),
+        };
+        *held += 1;
+        if *held > 1 {
+            return None;
+        }
+        if input == Input::SoftDrop {
+            self.soft_drop = Some(Duration::from_secs(0));
+        } else {
+            self.shift = Some(Repeat::new(input));
+        }
+        return Some(input);
+    }
+
+    /// Stops repeating `input` once no key bound to it is held.
+    pub fn release(&mut self, input: Input) {
+        let held = match input {
+            Input::MoveLeft => &mut self.left_held,
+            Input::MoveRight => &mut self.right_held,
+            Input::SoftDrop => &mut self.soft_drop_held,
+            _ => return,
+        };
+        *held = held.saturating_sub(1);
+        if *held > 0 {
+            return;
+        }
+        if input == Input::SoftDrop {
+            self.soft_drop = None;
+            return;
+        }
+        // Releasing the side that lost to the other changes nothing.
+        if self
+            .shift
+            .as_ref()
+            .map_or(false, |shift| shift.input != input)
+        {
+            return;
+        }
+        // Fall back to the other side if it is still held, charging DAS
+        // again from scratch.
+        self.shift = if self.left_held > 0 {
+            Some(Repeat::new(Input::MoveLeft))
+        } else if self.right_held > 0 {
+            Some(Repeat::new(Input::MoveRight))
+        } else {
+            None
+        };
+    }
+
+    /// Advances the held keys by `dt` and returns the inputs that became due,
+    /// with soft drop repeating `sdf` times per `gravity` interval. Gravity
+    /// may change between ticks; only the time left over carries on.
+    pub fn tick(&mut self, dt: Duration, gravity: Duration) -> Vec<Input> {
+        let mut inputs = vec![];
+        let handling = self.handling;
+        if let Some(shift) = self.shift.as_mut() {
+            let n = if handling.arr == Duration::from_secs(0) {
+                // Once DAS has charged, every tick shifts to the wall, so the
+                // mino stays there after spawning or rotating away from it.
+                shift.elapsed += dt;
+                if shift.elapsed < handling.das {
+                    0
+                } else {
+                    MAX_BOARD_WIDTH as u32
+                }
+            } else {
+                shift.advance(dt, |elapsed| {
+                    if elapsed < handling.das {
+                        0
+                    } else {
+                        1 + ((elapsed - handling.das).as_nanos() / handling.arr.as_nanos()) as u32
+                    }
+                })
+            };
+            for _ in (0..n).into_iter() {
+                inputs.push(shift.input);
+            }
+        }
+        if let Some(timer) = self.soft_drop.as_mut() {
+            let interval = (gravity / handling.sdf.max(1)).max(Duration::from_nanos(1));
+            *timer += dt;
+            while *timer >= interval {
+                *timer -= interval;
+                inputs.push(Input::SoftDrop);
+            }
+        }
+        return inputs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+    const GRAVITY: Duration = Duration::from_secs(1);
+
+    fn handling(das: u64, arr: u64, sdf: u32) -> Handling {
+        Handling {
+            das: MS * das as u32,
+            arr: MS * arr as u32,
+            sdf: sdf,
+        }
+    }
+
+    /// The inputs due over `ms` milliseconds, ticked one at a time.
+    fn tick_for(auto_repeat: &mut AutoRepeat, ms: u32, gravity: Duration) -> Vec<Input> {
+        return (0..ms)
+            .into_iter()
+            .flat_map(|_| auto_repeat.tick(MS, gravity))
+            .collect();
+    }
+
+    #[test]
+    fn shifts_repeat_after_das_at_arr() {
+        let mut auto_repeat = AutoRepeat::new(handling(100, 20, 20));
+        assert_eq!(auto_repeat.press(Input::MoveLeft), Some(Input::MoveLeft));
+        assert_eq!(auto_repeat.press(Input::HardDrop), Some(Input::HardDrop));
+        assert!(tick_for(&mut auto_repeat, 99, GRAVITY).is_empty());
+        assert_eq!(
+            tick_for(&mut auto_repeat, 1, GRAVITY),
+            vec![Input::MoveLeft]
+        );
+        assert_eq!(tick_for(&mut auto_repeat, 60, GRAVITY).len(), 3);
+        auto_repeat.release(Input::MoveLeft);
+        assert!(tick_for(&mut auto_repeat, 200, GRAVITY).is_empty());
+    }
+
+    #[test]
+    fn zero_arr_shifts_to_the_wall_every_tick() {
+        let mut auto_repeat = AutoRepeat::new(handling(100, 0, 20));
+        auto_repeat.press(Input::MoveRight);
+        assert!(tick_for(&mut auto_repeat, 99, GRAVITY).is_empty());
+        let shifts = tick_for(&mut auto_repeat, 1, GRAVITY);
+        assert_eq!(shifts, vec![Input::MoveRight; MAX_BOARD_WIDTH]);
+        assert_eq!(
+            tick_for(&mut auto_repeat, 1, GRAVITY),
+            vec![Input::MoveRight; MAX_BOARD_WIDTH]
+        );
+        auto_repeat.release(Input::MoveRight);
+        assert!(tick_for(&mut auto_repeat, 100, GRAVITY).is_empty());
+    }
+
+    #[test]
+    fn latest_side_wins_and_the_other_takes_over_on_release() {
+        let mut auto_repeat = AutoRepeat::new(handling(100, 20, 20));
+        auto_repeat.press(Input::MoveLeft);
+        assert_eq!(auto_repeat.press(Input::MoveRight), Some(Input::MoveRight));
+        assert_eq!(
+            tick_for(&mut auto_repeat, 100, GRAVITY),
+            vec![Input::MoveRight]
+        );
+        auto_repeat.release(Input::MoveRight);
+        assert!(tick_for(&mut auto_repeat, 99, GRAVITY).is_empty());
+        assert_eq!(
+            tick_for(&mut auto_repeat, 1, GRAVITY),
+            vec![Input::MoveLeft]
+        );
+    }
+
+    #[test]
+    fn releasing_the_losing_side_keeps_the_shift_going() {
+        let mut auto_repeat = AutoRepeat::new(handling(100, 20, 20));
+        auto_repeat.press(Input::MoveLeft);
+        auto_repeat.press(Input::MoveRight);
+        assert_eq!(tick_for(&mut auto_repeat, 130, GRAVITY).len(), 2);
+        auto_repeat.release(Input::MoveLeft);
+        assert_eq!(
+            tick_for(&mut auto_repeat, 90, GRAVITY),
+            vec![Input::MoveRight; 5]
+        );
+    }
+
+    #[test]
+    fn soft_drop_repeats_sdf_times_per_gravity_interval() {
+        let mut auto_repeat = AutoRepeat::new(handling(100, 20, 20));
+        assert_eq!(auto_repeat.press(Input::SoftDrop), Some(Input::SoftDrop));
+        assert_eq!(tick_for(&mut auto_repeat, 1000, GRAVITY).len(), 20);
+        auto_repeat.release(Input::SoftDrop);
+        assert!(tick_for(&mut auto_repeat, 1000, GRAVITY).is_empty());
+    }
+
+    #[test]
+    fn faster_gravity_does_not_make_soft_drop_catch_up() {
+        let mut auto_repeat = AutoRepeat::new(handling(100, 20, 20));
+        auto_repeat.press(Input::SoftDrop);
+        assert_eq!(tick_for(&mut auto_repeat, 1000, GRAVITY).len(), 20);
+        // After a level-up only the new rate applies from here on.
+        assert_eq!(tick_for(&mut auto_repeat, 10, GRAVITY / 2).len(), 0);
+        assert_eq!(tick_for(&mut auto_repeat, 500, GRAVITY / 2).len(), 20);
+    }
+
+    #[test]
+    fn action_stays_held_while_any_of_its_keys_is() {
+        let mut auto_repeat = AutoRepeat::new(handling(100, 20, 20));
+        assert_eq!(auto_repeat.press(Input::MoveLeft), Some(Input::MoveLeft));
+        assert_eq!(auto_repeat.press(Input::MoveLeft), None);
+        auto_repeat.release(Input::MoveLeft);
+        assert_eq!(
+            tick_for(&mut auto_repeat, 100, GRAVITY),
+            vec![Input::MoveLeft]
+        );
+        auto_repeat.release(Input::MoveLeft);
+        assert!(tick_for(&mut auto_repeat, 100, GRAVITY).is_empty());
+
+        auto_repeat.press(Input::SoftDrop);
+        auto_repeat.press(Input::SoftDrop);
+        auto_repeat.release(Input::SoftDrop);
+        assert_eq!(tick_for(&mut auto_repeat, 100, GRAVITY).len(), 2);
+    }
+}
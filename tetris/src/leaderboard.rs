@@ -0,0 +1,256 @@
+//! Best results of each mode, kept in a small text file between sessions.
+//!
+//! Each line holds one entry as `<score> <lines> <time_ms> <date> <seed>`,
+//! with the date in seconds since the Unix epoch, e.g.
+//!
+//! ```text
+//! 12400 40 83456 1760745600 1234
+//! ```
+//!
+//! Lines that cannot be read are skipped rather than failing the whole file,
+//! so a damaged file costs at most the entries on the damaged lines.
+
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::engine::Game;
+use crate::mode::Mode;
+
+/// How many entries a leaderboard keeps.
+pub const MAX_ENTRIES: usize = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub score: u64,
+    pub lines: u32,
+    pub time: Duration,
+    /// Seconds since the Unix epoch.
+    pub date: u64,
+    pub seed: u64,
+}
+
+impl Entry {
+    /// The result of `game`, finished now.
+    pub fn from_game(game: &Game) -> Self {
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Entry {
+            score: game.score().points(),
+            lines: game.score().lines(),
+            time: game.elapsed(),
+            date: date,
+            seed: game.seed(),
+        }
+    }
+
+    fn to_line(&self) -> String {
+        return format!(
+            "{} {} {} {} {}",
+            self.score,
+            self.lines,
+            self.time.as_millis(),
+            self.date,
+            self.seed
+        );
+    }
+
+    fn from_line(line: &str) -> Option<Entry> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() != 5 {
+            return None;
+        }
+        return Some(Entry {
+            score: words[0].parse().ok()?,
+            lines: words[1].parse().ok()?,
+            time: Duration::from_millis(words[2].parse().ok()?),
+            date: words[3].parse().ok()?,
+            seed: words[4].parse().ok()?,
+        });
+    }
+}
+
+/// The best entries of one mode, best first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Leaderboard {
+    mode: Mode,
+    entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    pub fn new(mode: Mode) -> Self {
+        Leaderboard {
+            mode: mode,
+            entries: vec![],
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        return self.mode;
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        return &self.entries;
+    }
+
+    /// Whether a game ending like `game` earns a place on the board at all:
+    /// only completed sprints have a time worth ranking.
+    pub fn qualifies(&self, game: &Game) -> bool {
+        match self.mode {
+            Mode::Sprint => self.mode.is_complete(game),
+            Mode::Marathon | Mode::Ultra => true,
+        }
+    }
+
+    /// Adds `entry` and returns its rank from 0, or `None` if it did not
+    /// make the board.
+    pub fn submit(&mut self, entry: Entry) -> Option<usize> {
+        let mode = self.mode;
+        let rank = self
+            .entries
+            .iter()
+            .position(|e| compare(mode, &entry, e) == Ordering::Less)
+            .unwrap_or(self.entries.len());
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        return Some(rank);
+    }
+
+    /// Loads the board of `mode` from `path`. A missing file is an empty
+    /// board, and unreadable lines, including ones that are not valid UTF-8,
+    /// are skipped.
+    pub fn load<P: AsRef<Path>>(path: P, mode: Mode) -> io::Result<Leaderboard> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err),
+        };
+        let text = String::from_utf8_lossy(&bytes);
+        let mut board = Leaderboard::new(mode);
+        for entry in text.lines().filter_map(Entry::from_line) {
+            board.submit(entry);
+        }
+        return Ok(board);
+    }
+
+    /// Writes the board to a temporary file first and moves it over `path`,
+    /// so a crash mid-write leaves the old file intact.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        let text: String = self.entries.iter().map(|e| e.to_line() + "\n").collect();
+        fs::write(&tmp, text)?;
+        return fs::rename(&tmp, path);
+    }
+}
+
+/// Orders entries of `mode` best first: fastest time in Sprint, highest score
+/// otherwise. Ties go to the older entry.
+fn compare(mode: Mode, a: &Entry, b: &Entry) -> Ordering {
+    let order = match mode {
+        Mode::Sprint => a.time.cmp(&b.time),
+        Mode::Marathon | Mode::Ultra => b.score.cmp(&a.score),
+    };
+    return order.then(a.date.cmp(&b.date));
+}
+
+/// Formats `date`, in seconds since the Unix epoch, as `YYYY-MM-DD` (UTC).
+pub fn format_date(date: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm, for days since 1970-01-01.
+    let days = (date / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    return format!("{:04}-{:02}-{:02}", year, month, day);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A path in the temporary directory unique to this test run.
+    fn temp_path(name: &str) -> PathBuf {
+        let file = format!("tetris-test-{}-{}.scores", std::process::id(), name);
+        return std::env::temp_dir().join(file);
+    }
+
+    fn entry(score: u64, date: u64) -> Entry {
+        Entry {
+            score: score,
+            lines: (score / 100) as u32,
+            time: Duration::from_millis(score * 7),
+            date: date,
+            seed: score + 1,
+        }
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let mut board = Leaderboard::new(Mode::Marathon);
+        for (i, &score) in [300, 1200, 800].iter().enumerate() {
+            board.submit(entry(score, i as u64));
+        }
+        let path = temp_path("round-trip");
+        board.save(&path).unwrap();
+        let read = Leaderboard::load(&path, Mode::Marathon).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, board);
+        let scores: Vec<u64> = read.entries().iter().map(|e| e.score).collect();
+        assert_eq!(scores, vec![1200, 800, 300]);
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_board() {
+        let read = Leaderboard::load(temp_path("missing"), Mode::Sprint).unwrap();
+        assert!(read.entries().is_empty());
+    }
+
+    #[test]
+    fn skips_garbage_lines() {
+        let path = temp_path("garbage");
+        let text = "500 5 3500 1 501\nnot an entry\n1 2 3\n900 9 6300 2 x\n700 7 4900 3 701\n";
+        fs::write(&path, text).unwrap();
+        let read = Leaderboard::load(&path, Mode::Marathon).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.entries(), &[entry(700, 3), entry(500, 1)]);
+    }
+
+    #[test]
+    fn skips_lines_that_are_not_utf8() {
+        let path = temp_path("not-utf8");
+        let mut bytes = b"500 5 3500 1 501\n".to_vec();
+        bytes.extend_from_slice(b"90\xff0 9 6300 2 10\n");
+        bytes.extend_from_slice(b"700 7 4900 3 701\n");
+        fs::write(&path, bytes).unwrap();
+        let read = Leaderboard::load(&path, Mode::Marathon).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.entries(), &[entry(700, 3), entry(500, 1)]);
+    }
+
+    #[test]
+    fn keeps_only_the_best_entries() {
+        let mut board = Leaderboard::new(Mode::Ultra);
+        for score in (1..=15).into_iter() {
+            board.submit(entry(score * 100, score));
+        }
+        assert_eq!(board.entries().len(), MAX_ENTRIES);
+        assert_eq!(board.entries()[0].score, 1500);
+        assert_eq!(board.entries()[MAX_ENTRIES - 1].score, 600);
+        assert_eq!(board.submit(entry(100, 99)), None);
+        assert_eq!(board.submit(entry(1000, 99)), Some(6));
+        assert_eq!(board.entries().len(), MAX_ENTRIES);
+    }
+}
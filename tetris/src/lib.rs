@@ -1,11 +1,46 @@
+use nannou::color::IntoLinSrgba;
 use nannou::prelude::*;
-use nannou::rand::seq::SliceRandom;
-use nannou::rand::thread_rng;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+pub mod bot;
+pub mod challenge;
+pub mod config;
+pub mod engine;
+pub mod fumen;
+pub mod handling;
+pub mod leaderboard;
+pub mod mode;
+pub mod net;
+pub mod puzzle;
+pub mod randomizer;
+pub mod replay;
+pub mod versus;
+
+use engine::{
+    Block, Board, Cell, Delays, Dimensions, Game, Input, Mino, Phase, Score, Shape, MAX_PREVIEW,
+};
+
+use bot::{Bot, Weights};
+use config::{Action, Bindings, Config};
+use handling::AutoRepeat;
+use leaderboard::{format_date, Entry, Leaderboard};
+use mode::{format_time, Mode, MODES};
+use randomizer::RandomizerKind;
+use replay::{Playback, Replay, FRAME_TIME};
 
 const BLOCK_SIZE: f32 = 30.0;
-const BOARD_WIDTH: usize = 10;
-const BOARD_HEIGHT: usize = 24;
-const BOARD_HEIGHT_PLAYABLE: usize = 20;
+/// Columns left of the board holding the hold mino and the counts.
+const LEFT_PANEL: f32 = 8.0;
+/// Columns right of the board holding the next minos.
+const RIGHT_PANEL: f32 = 7.0;
+/// Rows the hold label and the counts take, from the top of the board down.
+const COUNTS_ROWS: i32 = 15;
+const DEFAULT_PREVIEW_LEN: usize = 5;
+const DEFAULT_CONFIG_PATH: &str = "tetris.toml";
+/// How many leaderboard entries the game-over screen lists.
+const SHOWN_ENTRIES: usize = 5;
 
 const T_WHITE: Rgb8 = WHITESMOKE;
 const T_BG: Rgb8 = DARKGRAY;
@@ -13,6 +48,7 @@ const T_BG: Rgb8 = DARKGRAY;
 const C_STR: Rgb8 = NAVY;
 
 const C_LOCATED: Rgb8 = SILVER;
+const C_GARBAGE: Rgb8 = GRAY;
 
 const C_TMINO: Rgb8 = ROYALBLUE;
 const C_SMINO: Rgb8 = CORAL;
@@ -22,401 +58,932 @@ const C_JMINO: Rgb8 = OLIVE;
 const C_LMINO: Rgb8 = SKYBLUE;
 const C_IMINO: Rgb8 = ORANGERED;
 
-pub struct Model {
-    mino: Mino,
-    board: Board,
-    next_minos: Vec<Mino>,
-    deleted_lines: u32,
-    active: bool,
-    fc: i32,
+const GHOST_ALPHA: u8 = 80;
+const OVERLAY_ALPHA: u8 = 200;
+const FLASH_ALPHA: u8 = 180;
+
+/// How long the bot waits after a mino spawns before placing it, so its play
+/// can be followed.
+const BOT_THINK_TIME: Duration = Duration::from_millis(200);
+
+/// How long the cells of a locked mino flash.
+const LOCK_FLASH_TIME: Duration = Duration::from_millis(150);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    Title,
+    Playing,
+    Paused,
+    /// Waiting for the player to confirm a restart requested mid-game.
+    /// `paused` tells which state cancelling returns to.
+    ConfirmRestart {
+        paused: bool,
+    },
+    GameOver,
 }
 
-pub fn model(_app: &App) -> Model {
-    return get_initial_model();
+/// A flash fading over the cells of the mino that locked last.
+struct LockFlash {
+    blocks: Vec<Block>,
+    elapsed: Duration,
+}
+
+pub struct Model {
+    game: Game,
+    /// The mode the current or just finished game is played in.
+    mode: Mode,
+    /// The mode of the next game, picked on the title and game-over screens.
+    next_mode: Mode,
+    dimensions: Dimensions,
+    delays: Delays,
+    all_spin: bool,
+    dealing: Dealing,
+    /// The board every game starts on when one was loaded with `--board` or
+    /// `--fumen`.
+    board: Option<Board>,
+    preview_len: usize,
+    frame: u64,
+    frame_timer: Duration,
+    replay: Replay,
+    playback: Option<Playback>,
+    held_keys: HashSet<Key>,
+    auto_repeat: AutoRepeat,
+    bindings: Bindings,
+    state: State,
+    leaderboard: Leaderboard,
+    /// Where the last finished game placed on the leaderboard.
+    rank: Option<usize>,
+    lock_flash: Option<LockFlash>,
+    /// `Game::pieces` when the last lock flash started.
+    flashed_pieces: u32,
+    /// Plays instead of the player when started with `--bot`.
+    bot: Option<Bot>,
+    bot_timer: Duration,
 }
 
-fn get_initial_model() -> Model {
-    let board = Board::new();
-    let mut minos = generate_mino_pool();
-    let mino = minos.remove(0);
+pub fn model(_app: &App) -> Model {
+    let config = load_config();
+    let playback = load_playback();
+    let dealing = load_dealing();
+    let board = load_board(config.dimensions);
+    let (game, mode, state) = match &playback {
+        Some(playback) => (
+            playback.replay().new_game(),
+            playback.replay().mode(),
+            State::Playing,
+        ),
+        None => (
+            new_game(
+                start_board(config.dimensions, &board),
+                config.delays,
+                config.all_spin,
+                dealing,
+            ),
+            load_mode(),
+            State::Title,
+        ),
+    };
     Model {
-        mino: mino,
+        replay: Replay::for_game(&game, mode),
+        game: game,
+        mode: mode,
+        next_mode: mode,
+        dimensions: config.dimensions,
+        delays: config.delays,
+        all_spin: config.all_spin,
+        dealing: dealing,
         board: board,
-        next_minos: minos,
-        deleted_lines: 0,
-        active: true,
-        fc: 0,
+        preview_len: DEFAULT_PREVIEW_LEN,
+        frame: 0,
+        frame_timer: Duration::from_secs(0),
+        playback: playback,
+        held_keys: HashSet::new(),
+        auto_repeat: AutoRepeat::new(config.handling),
+        bindings: config.bindings,
+        state: state,
+        leaderboard: load_leaderboard(mode),
+        rank: None,
+        lock_flash: None,
+        flashed_pieces: 0,
+        bot: load_bot(),
+        bot_timer: Duration::from_secs(0),
     }
 }
 
-pub fn event(_app: &App, model: &mut Model, event: Event) {
-    match event {
-        Event::WindowEvent {
-            id: _,
-            simple: some_we,
-        } => {
-            if let Some(we) = some_we {
-                window_event(model, we);
-            }
-        }
-        _other => {}
+/// The value following `flag` on the command line.
+fn arg_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let i = args.iter().position(|arg| arg == flag)?;
+    return args.get(i + 1).cloned();
+}
+
+fn has_flag(flag: &str) -> bool {
+    return std::env::args().any(|arg| arg == flag);
+}
+
+/// The bot asked for with `--bot`, weighted by `--weights` if given.
+fn load_bot() -> Option<Bot> {
+    if !has_flag("--bot") {
+        return None;
     }
+    let weights = match arg_value("--weights").map(|text| Weights::parse(&text)) {
+        Some(Ok(weights)) => weights,
+        Some(Err(err)) => {
+            eprintln!("{}, using the default weights", err);
+            Weights::default()
+        }
+        None => Weights::default(),
+    };
+    return Some(Bot::new(weights));
 }
 
-fn window_event(model: &mut Model, event: WindowEvent) {
-    match event {
-        KeyPressed(key) => match key {
-            Key::A | Key::Left => model.mino.move_left_if_possible(&model.board),
-            Key::S | Key::Down => model.mino.move_down_if_possible(&model.board),
-            Key::D | Key::Right => model.mino.move_right_if_possible(&model.board),
-            Key::W => {
-                model.mino.y += 1;
-            }
-            Key::R => model.mino.rotate_if_possible(&model.board),
-            Key::Space => {
-                let new_mdl = get_initial_model();
-                model.mino = new_mdl.mino;
-                model.board = new_mdl.board;
-                model.next_minos = new_mdl.next_minos;
-                model.deleted_lines = new_mdl.deleted_lines;
-                model.active = new_mdl.active;
-                model.fc = new_mdl.fc;
-            }
-            _ => {}
-        },
-        _other => {}
+/// Loads the config given as `--config <path>`, or `tetris.toml` if it
+/// exists, falling back to the defaults when it is missing or invalid.
+fn load_config() -> Config {
+    let path = match arg_value("--config") {
+        Some(path) => path,
+        None if Path::new(DEFAULT_CONFIG_PATH).exists() => DEFAULT_CONFIG_PATH.to_string(),
+        None => return Config::default(),
+    };
+    match Config::load(&path) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("could not load config {}, using defaults: {}", path, err);
+            Config::default()
+        }
     }
 }
 
-pub fn update(_app: &App, model: &mut Model, _update: Update) {
-    if model.active && model.fc % 30 == 0 {
-        if !model.mino.can_move_down(&model.board) {
-            model.board.put_mino(&model.mino);
-            let n_delete = model.board.delete_line();
-            model.deleted_lines += n_delete;
-            let next_mino: Mino = get_next_mino(model);
-            if can_spawn(&next_mino, &model.board) {
-                model.mino = next_mino;
-            } else {
-                model.active = false;
-            }
+/// The mode given as `--mode <name>`, marathon by default.
+fn load_mode() -> Mode {
+    let name = match arg_value("--mode") {
+        Some(name) => name,
+        None => return Mode::Marathon,
+    };
+    match Mode::from_name(&name) {
+        Some(mode) => mode,
+        None => {
+            eprintln!("unknown mode {}, playing marathon", name);
+            Mode::Marathon
         }
-        model.mino.move_down();
     }
-    if model.active {
-        model.fc += 1;
+}
+
+/// How pieces are dealt: the randomizer given as `--randomizer <name>`,
+/// 7-bags by default, seeded with `--seed <n>` or a new random seed each game.
+#[derive(Copy, Clone)]
+struct Dealing {
+    kind: RandomizerKind,
+    seed: Option<u64>,
+}
+
+impl Dealing {
+    fn seed(&self) -> u64 {
+        return self.seed.unwrap_or_else(rand::random);
     }
 }
 
-fn get_next_mino(model: &mut Model) -> Mino {
-    match model.next_minos.pop() {
-        Some(mino) => mino,
-        None => {
-            model.next_minos = generate_mino_pool();
-            let mino = model.next_minos.remove(0);
-            return mino;
+fn load_dealing() -> Dealing {
+    let kind = match arg_value("--randomizer") {
+        Some(name) => RandomizerKind::from_name(&name).unwrap_or_else(|| {
+            eprintln!("unknown randomizer {}, dealing 7-bags", name);
+            RandomizerKind::Bag7
+        }),
+        None => RandomizerKind::Bag7,
+    };
+    let seed = match arg_value("--seed").map(|text| text.parse()) {
+        Some(Ok(seed)) => Some(seed),
+        Some(Err(_)) => {
+            eprintln!("invalid seed, using a random one");
+            None
+        }
+        None => None,
+    };
+    return Dealing {
+        kind: kind,
+        seed: seed,
+    };
+}
+
+/// The board given as a file of rows with `--board <path>`, sized by the
+/// config, or as a fumen with `--fumen <code>`, always of the default size.
+fn load_board(dimensions: Dimensions) -> Option<Board> {
+    let loaded = if let Some(path) = arg_value("--board") {
+        std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Board::from_text(dimensions, &text))
+            .map_err(|err| format!("could not load board {}: {}", path, err))
+    } else if let Some(code) = arg_value("--fumen") {
+        let board = fumen::decode(&code).map_err(|err| format!("could not load fumen: {}", err));
+        if board.is_ok() && dimensions != Dimensions::default() {
+            eprintln!("fumen boards have the default size, ignoring the configured one");
+        }
+        board
+    } else {
+        return None;
+    };
+    match loaded {
+        Ok(board) => Some(board),
+        Err(err) => {
+            eprintln!("{}, starting on an empty board", err);
+            None
         }
     }
 }
 
-fn can_spawn(mino: &Mino, board: &Board) -> bool {
-    let blocks = mino.get_blocks();
-    return blocks.iter().all(|b| board.is_blank_at(b.x, b.y));
+fn start_board(dimensions: Dimensions, board: &Option<Board>) -> Board {
+    match board {
+        Some(board) => board.clone(),
+        None => Board::from_rows(dimensions, &[]),
+    }
 }
 
-fn generate_mino_pool() -> Vec<Mino> {
-    let mut minos = vec![
-        Mino::new(5, 20, 0, Shape::T),
-        Mino::new(5, 20, 0, Shape::I),
-        Mino::new(5, 20, 0, Shape::J),
-        Mino::new(5, 20, 0, Shape::L),
-        Mino::new(5, 20, 0, Shape::S),
-        Mino::new(5, 20, 0, Shape::Z),
-        Mino::new(5, 20, 0, Shape::O),
-    ];
-    let mut rng = thread_rng();
-    minos.shuffle(&mut rng);
-    return minos;
+fn leaderboard_path(mode: Mode) -> String {
+    return format!("tetris-{}.scores", mode.name());
 }
 
-pub fn view(app: &App, model: &Model, frame: Frame) {
-    frame.clear(T_BG);
-    let draw = app.draw();
-    model.board.draw(&draw);
-    if model.active {
-        model.mino.draw(&draw);
+fn load_leaderboard(mode: Mode) -> Leaderboard {
+    let path = leaderboard_path(mode);
+    match Leaderboard::load(&path, mode) {
+        Ok(leaderboard) => leaderboard,
+        Err(err) => {
+            eprintln!("could not load leaderboard {}: {}", path, err);
+            Leaderboard::new(mode)
+        }
     }
-    show_counts(model.deleted_lines, &draw);
-    draw.to_frame(app, &frame).unwrap();
 }
 
-fn show_counts(dc: u32, draw: &Draw) {
-    let x = 0.0;
-    let y = BLOCK_SIZE * (-1 - BOARD_HEIGHT_PLAYABLE as i32 / 2) as f32;
-    let str = format!("You deleted {} lines.", dc);
-    draw.text(&str).x_y(x, y).color(C_STR);
+/// Picks the mode of the next game, leaving the results of the last one on
+/// screen until it starts.
+fn select_mode(model: &mut Model, mode: Mode) {
+    model.next_mode = mode;
 }
 
-struct Block {
-    x: i32,
-    y: i32,
-    color: Rgb8,
+/// Whether results go on the leaderboard: only games a player played live
+/// from an empty board, with the standard board, delays, rules and dealing,
+/// so every entry of a mode is comparable.
+fn is_ranked(model: &Model) -> bool {
+    let standard = model.dimensions == Dimensions::default()
+        && model.delays == Delays::default()
+        && !model.all_spin
+        && model.dealing.kind == RandomizerKind::Bag7
+        && model.dealing.seed.is_none();
+    return standard && model.playback.is_none() && model.board.is_none() && model.bot.is_none();
 }
 
-impl Block {
-    fn new(x: i32, y: i32, color: Rgb8) -> Self {
-        Block {
-            x: x,
-            y: y,
-            color: color,
+/// Ends the game and enters its result on the leaderboard if it is ranked.
+fn finish_game(model: &mut Model) {
+    model.state = State::GameOver;
+    if !is_ranked(model) {
+        return;
+    }
+    if !model.leaderboard.qualifies(&model.game) {
+        return;
+    }
+    model.rank = model.leaderboard.submit(Entry::from_game(&model.game));
+    if model.rank.is_some() {
+        let path = leaderboard_path(model.mode);
+        if let Err(err) = model.leaderboard.save(&path) {
+            eprintln!("could not save leaderboard {}: {}", path, err);
         }
     }
+}
 
-    fn draw(&self, draw: &Draw) {
-        let loc_x = BLOCK_SIZE * (self.x - BOARD_WIDTH as i32 / 2) as f32;
-        let loc_y = BLOCK_SIZE * (self.y - BOARD_HEIGHT_PLAYABLE as i32 / 2) as f32;
-        let block_size = BLOCK_SIZE * 0.95;
-        draw.rect()
-            .x_y(loc_x, loc_y)
-            .w(block_size)
-            .h(block_size)
-            .color(self.color);
-    }
-
-    fn rotate(&self, rot: i32) -> Block {
-        let mut x = self.x;
-        let mut y = self.y;
-        for _ in (0..rot).into_iter() {
-            let tmp = x;
-            x = -y;
-            y = tmp;
+/// Loads the replay given as `--replay <path>` on the command line.
+fn load_playback() -> Option<Playback> {
+    let path = arg_value("--replay")?;
+    match Replay::load(&path) {
+        Ok(replay) => Some(Playback::new(replay)),
+        Err(err) => {
+            eprintln!("could not load replay {}: {}", path, err);
+            None
         }
-        return Block::new(x, y, self.color);
     }
+}
+
+fn new_game(board: Board, delays: Delays, all_spin: bool, dealing: Dealing) -> Game {
+    let mut game = Game::with_board(board, dealing.kind, dealing.seed());
+    game.set_delays(delays);
+    game.set_all_spin(all_spin);
+    return game;
+}
 
-    fn shift(&self, shift_x: i32, shift_y: i32) -> Block {
-        return Block::new(self.x + shift_x, self.y + shift_y, self.color);
+fn restart(model: &mut Model) {
+    if model.next_mode != model.mode {
+        model.mode = model.next_mode;
+        model.leaderboard = load_leaderboard(model.mode);
     }
+    model.game = new_game(
+        start_board(model.dimensions, &model.board),
+        model.delays,
+        model.all_spin,
+        model.dealing,
+    );
+    model.replay = Replay::for_game(&model.game, model.mode);
+    model.frame = 0;
+    model.frame_timer = Duration::from_secs(0);
+    model.playback = None;
+    model.state = State::Playing;
+    model.rank = None;
+    model.lock_flash = None;
+    model.flashed_pieces = 0;
+    model.bot_timer = Duration::from_secs(0);
 }
 
-#[derive(Copy, Clone)]
-enum Shape {
-    T,
-    Z,
-    S,
-    L,
-    J,
-    O,
-    I,
-}
-
-struct Mino {
-    x: i32,
-    y: i32,
-    rot: i32,
-    shape: Shape,
-}
-
-impl Mino {
-    fn new(x: i32, y: i32, rot: i32, shape: Shape) -> Self {
-        Mino {
-            x: x,
-            y: y,
-            rot: rot,
-            shape: shape,
-        }
+/// Restarts right away when no game is in progress, and asks for
+/// confirmation otherwise.
+fn request_restart(model: &mut Model) {
+    match model.state {
+        State::Playing => model.state = State::ConfirmRestart { paused: false },
+        State::Paused => model.state = State::ConfirmRestart { paused: true },
+        State::Title | State::GameOver | State::ConfirmRestart { .. } => restart(model),
     }
+}
 
-    fn get_blocks(&self) -> Vec<Block> {
-        let blocks = match self.shape {
-            Shape::T => vec![
-                Block::new(-1, 0, C_TMINO),
-                Block::new(0, 0, C_TMINO),
-                Block::new(0, -1, C_TMINO),
-                Block::new(1, 0, C_TMINO),
-            ],
-            Shape::S => vec![
-                Block::new(-1, -1, C_SMINO),
-                Block::new(0, -1, C_SMINO),
-                Block::new(0, 0, C_SMINO),
-                Block::new(1, 0, C_SMINO),
-            ],
-            Shape::Z => vec![
-                Block::new(-1, 0, C_ZMINO),
-                Block::new(0, 0, C_ZMINO),
-                Block::new(0, -1, C_ZMINO),
-                Block::new(1, -1, C_ZMINO),
-            ],
-            Shape::J => vec![
-                Block::new(-1, -2, C_JMINO),
-                Block::new(-1, -1, C_JMINO),
-                Block::new(-1, 0, C_JMINO),
-                Block::new(0, 0, C_JMINO),
-            ],
-            Shape::L => vec![
-                Block::new(0, -2, C_LMINO),
-                Block::new(0, -1, C_LMINO),
-                Block::new(-1, 0, C_LMINO),
-                Block::new(0, 0, C_LMINO),
-            ],
-            Shape::O => vec![
-                Block::new(0, 0, C_OMINO),
-                Block::new(-1, 0, C_OMINO),
-                Block::new(0, -1, C_OMINO),
-                Block::new(-1, -1, C_OMINO),
-            ],
-            Shape::I => vec![
-                Block::new(-2, 0, C_IMINO),
-                Block::new(-1, 0, C_IMINO),
-                Block::new(0, 0, C_IMINO),
-                Block::new(1, 0, C_IMINO),
-            ],
+fn cancel_restart(model: &mut Model) {
+    if let State::ConfirmRestart { paused } = model.state {
+        model.state = if paused {
+            State::Paused
+        } else {
+            State::Playing
         };
-        let rot = (self.rot + 10000) % 4;
-        let ret: Vec<Block> = blocks
-            .iter()
-            .map(|b| b.rotate(rot))
-            .map(|b| b.shift(self.x, self.y))
-            .collect();
-        return ret;
-    }
-
-    fn can_move_left(&self, board: &Board) -> bool {
-        let mino: Mino = Mino::new(self.x - 1, self.y, self.rot, self.shape);
-        return mino
-            .get_blocks()
-            .iter()
-            .all(|b| board.is_blank_at(b.x, b.y));
-    }
-
-    fn can_move_right(&self, board: &Board) -> bool {
-        let mino: Mino = Mino::new(self.x + 1, self.y, self.rot, self.shape);
-        return mino
-            .get_blocks()
-            .iter()
-            .all(|b| board.is_blank_at(b.x, b.y));
-    }
-
-    fn can_move_down(&self, board: &Board) -> bool {
-        let mino: Mino = Mino::new(self.x, self.y - 1, self.rot, self.shape);
-        return mino
-            .get_blocks()
-            .iter()
-            .all(|b| board.is_blank_at(b.x, b.y));
-    }
-
-    fn can_rotate(&self, board: &Board) -> bool {
-        let mino: Mino = Mino::new(self.x, self.y, self.rot + 1, self.shape);
-        return mino
-            .get_blocks()
-            .iter()
-            .all(|b| board.is_blank_at(b.x, b.y));
-    }
-
-    fn move_left_if_possible(&mut self, board: &Board) {
-        if self.can_move_left(board) {
-            self.x -= 1;
-        }
     }
+}
+
+fn toggle_pause(model: &mut Model) {
+    match model.state {
+        State::Playing => model.state = State::Paused,
+        State::Paused => model.state = State::Playing,
+        State::ConfirmRestart { .. } => cancel_restart(model),
+        State::Title | State::GameOver => {}
+    }
+}
 
-    fn move_right_if_possible(&mut self, board: &Board) {
-        if self.can_move_right(board) {
-            self.x += 1;
+/// Applies a player's input, unless a replay is being played back or the
+/// game is not running, and records it.
+fn apply_input(model: &mut Model, input: Input) {
+    if model.playback.is_some() || model.state != State::Playing {
+        return;
+    }
+    model.game.apply(input);
+    model.replay.record(model.frame, input);
+}
+
+/// Handles the press of a key bound to `input`, letting `AutoRepeat` take
+/// over the keys that repeat while held.
+fn press_input(model: &mut Model, input: Input) {
+    if model.bot.is_some() {
+        return;
+    }
+    if let Some(input) = model.auto_repeat.press(input) {
+        apply_input(model, input);
+    }
+}
+
+fn press_action(model: &mut Model, action: Action) {
+    match action.input() {
+        Some(input) => press_input(model, input),
+        None => match action {
+            Action::Pause => toggle_pause(model),
+            Action::Restart => request_restart(model),
+            _ => {}
+        },
+    }
+}
+
+fn save_replay(replay: &Replay) {
+    let path = format!("tetris-{}.replay", replay.seed());
+    match replay.save(&path) {
+        Ok(()) => println!("saved replay to {}", path),
+        Err(err) => eprintln!("could not save replay to {}: {}", path, err),
+    }
+}
+
+/// Saves the locked cells of `game` as text, and prints them as a fumen to
+/// paste into an editor when the board fits one.
+fn save_board(game: &Game) {
+    let path = format!("tetris-{}.board", game.seed());
+    match std::fs::write(&path, game.board().to_text()) {
+        Ok(()) => println!("saved board to {}", path),
+        Err(err) => eprintln!("could not save board to {}: {}", path, err),
+    }
+    if let Ok(fumen) = fumen::encode(game.board()) {
+        println!("{}", fumen);
+    }
+}
+
+pub fn event(_app: &App, model: &mut Model, event: Event) {
+    match event {
+        Event::WindowEvent {
+            id: _,
+            simple: some_we,
+        } => {
+            if let Some(we) = some_we {
+                window_event(model, we);
+            }
         }
+        _other => {}
     }
+}
+
+/// A key going down or coming up.
+enum KeyEvent {
+    Pressed(Key),
+    Released(Key),
+}
 
-    fn move_down_if_possible(&mut self, board: &Board) {
-        if self.can_move_down(board) {
-            self.y -= 1;
+/// The key event in `event`, if any. The operating system repeats
+/// KeyPressed while a key is held; only the first press counts.
+fn key_event(held_keys: &mut HashSet<Key>, event: WindowEvent) -> Option<KeyEvent> {
+    match event {
+        KeyPressed(key) if held_keys.insert(key) => Some(KeyEvent::Pressed(key)),
+        KeyReleased(key) => {
+            held_keys.remove(&key);
+            Some(KeyEvent::Released(key))
         }
+        _other => None,
+    }
+}
+
+/// Stops auto-repeating the input bound to `key`, if any.
+fn release_key(bindings: &Bindings, auto_repeat: &mut AutoRepeat, key: Key) {
+    if let Some(input) = bindings.action(key).and_then(|a| a.input()) {
+        auto_repeat.release(input);
+    }
+}
+
+/// Adds `since_last` to `timer` and takes the whole `FRAME_TIME` frames out
+/// of it, returning how many to step.
+fn due_frames(timer: &mut Duration, since_last: Duration) -> u32 {
+    *timer += since_last;
+    let mut frames = 0;
+    while *timer >= FRAME_TIME {
+        *timer -= FRAME_TIME;
+        frames += 1;
     }
+    return frames;
+}
 
-    fn move_down(&mut self) {
-        self.y -= 1;
+/// The inputs the held keys repeat over the next frame of `game`.
+fn frame_inputs(auto_repeat: &mut AutoRepeat, game: &Game) -> Vec<Input> {
+    return auto_repeat.tick(FRAME_TIME, game.gravity_interval());
+}
+
+fn window_event(model: &mut Model, event: WindowEvent) {
+    match key_event(&mut model.held_keys, event) {
+        Some(KeyEvent::Pressed(key)) => match model.bindings.action(key) {
+            Some(action) => press_action(model, action),
+            None => key_pressed(model, key),
+        },
+        Some(KeyEvent::Released(key)) => release_key(&model.bindings, &mut model.auto_repeat, key),
+        None => {}
     }
+}
 
-    fn rotate_if_possible(&mut self, board: &Board) {
-        if self.can_rotate(board) {
-            self.rot += 1;
+fn key_pressed(model: &mut Model, key: Key) {
+    let confirming = match model.state {
+        State::ConfirmRestart { .. } => true,
+        _ => false,
+    };
+    let choosing_mode = model.state == State::Title || model.state == State::GameOver;
+    match key {
+        Key::Y | Key::Return if confirming => restart(model),
+        Key::N if confirming => cancel_restart(model),
+        Key::Key1 if choosing_mode => select_mode(model, MODES[0]),
+        Key::Key2 if choosing_mode => select_mode(model, MODES[1]),
+        Key::Key3 if choosing_mode => select_mode(model, MODES[2]),
+        // Replays only record the dealing, not a loaded board.
+        Key::F2 if model.board.is_some() => eprintln!("cannot save replays on a loaded board"),
+        Key::F2 => save_replay(&model.replay),
+        Key::F3 => save_board(&model.game),
+        Key::Minus => {
+            model.preview_len = (model.preview_len - 1).max(1);
+        }
+        Key::Equals => {
+            model.preview_len = (model.preview_len + 1).min(MAX_PREVIEW);
         }
+        _ => {}
     }
+}
 
-    fn draw(&self, draw: &Draw) {
-        let blocks: Vec<Block> = self.get_blocks();
-        for b in blocks {
-            b.draw(&draw);
+/// Steps the game in fixed `FRAME_TIME` frames so recordings replay exactly.
+pub fn update(_app: &App, model: &mut Model, update: Update) {
+    if model.state != State::Playing {
+        return;
+    }
+    for _ in (0..due_frames(&mut model.frame_timer, update.since_last)).into_iter() {
+        if let Some(playback) = model.playback.as_mut() {
+            for input in playback.inputs_at(model.frame) {
+                model.game.apply(input);
+            }
+        }
+        bot_turn(model);
+        for input in frame_inputs(&mut model.auto_repeat, &model.game) {
+            apply_input(model, input);
+        }
+        model.game.step(FRAME_TIME);
+        model.frame += 1;
+        if model.mode.is_over(&model.game) {
+            finish_game(model);
+            return;
         }
     }
+    update_lock_flash(model, update.since_last);
 }
 
-struct Board {
-    blocks: [[i32; BOARD_WIDTH]; BOARD_HEIGHT],
+/// Lets the bot place the falling mino once it has fallen for
+/// `BOT_THINK_TIME`.
+fn bot_turn(model: &mut Model) {
+    let bot = match model.bot {
+        Some(bot) if model.playback.is_none() => bot,
+        _ => return,
+    };
+    if !model.game.is_falling() {
+        model.bot_timer = Duration::from_secs(0);
+        return;
+    }
+    model.bot_timer += FRAME_TIME;
+    if model.bot_timer < BOT_THINK_TIME {
+        return;
+    }
+    model.bot_timer = Duration::from_secs(0);
+    if let Some(placement) = bot.choose(&model.game) {
+        for input in placement.inputs {
+            apply_input(model, input);
+        }
+    }
 }
 
-impl Board {
-    fn new() -> Self {
-        let blocks = [[0; BOARD_WIDTH]; BOARD_HEIGHT];
-        Board { blocks: blocks }
+/// Fades the current lock flash, and starts a new one when a mino locked
+/// since the last update.
+fn update_lock_flash(model: &mut Model, since_last: Duration) {
+    if let Some(flash) = model.lock_flash.as_mut() {
+        flash.elapsed += since_last;
+        if flash.elapsed >= LOCK_FLASH_TIME {
+            model.lock_flash = None;
+        }
+    }
+    if model.game.pieces() != model.flashed_pieces {
+        model.flashed_pieces = model.game.pieces();
+        if let Some(mino) = model.game.last_locked() {
+            model.lock_flash = Some(LockFlash {
+                blocks: mino.get_blocks(),
+                elapsed: Duration::from_secs(0),
+            });
+        }
     }
+}
 
-    fn is_blank_at(&self, x: i32, y: i32) -> bool {
-        if x < 0 || x > BOARD_WIDTH as i32 - 1 {
-            return false;
+pub fn view(app: &App, model: &Model, frame: Frame) {
+    frame.clear(T_BG);
+    let board = model.game.board();
+    let draw = board_draw(app, board.dimensions());
+    draw_board(board, model.game.phase(), model.game.delays(), &draw);
+    if model.game.is_active() && model.game.is_falling() && model.state != State::Title {
+        draw_ghost(&model.game.ghost(), &draw);
+        draw_mino(model.game.mino(), &draw);
+    }
+    if let Some(flash) = &model.lock_flash {
+        draw_lock_flash(flash, &draw);
+    }
+    show_hold(model.game.hold(), model.game.can_hold(), board, &draw);
+    show_next(&model.game.preview(model.preview_len), board, &draw);
+    show_counts(
+        model.game.score(),
+        model.mode,
+        model.mode.clock(&model.game),
+        board,
+        &draw,
+    );
+    let (x, y) = block_location(&Block {
+        x: board.width() as i32 / 2,
+        y: -1,
+    });
+    if model.playback.is_some() {
+        draw.text("REPLAY").x_y(x, y).color(C_STR);
+    } else if model.bot.is_some() {
+        draw.text("BOT").x_y(x, y).color(C_STR);
+    }
+    let restart_key = key_names(&model.bindings, Action::Restart);
+    let pause_key = key_names(&model.bindings, Action::Pause);
+    let modes: Vec<String> = MODES
+        .iter()
+        .enumerate()
+        .map(|(i, mode)| format!("{} {}", i + 1, mode.name()))
+        .collect();
+    match model.state {
+        State::Title => show_overlay(
+            &[
+                "TETRIS".to_string(),
+                format!("MODE {}", model.next_mode.name().to_uppercase()),
+                modes.join("  "),
+                format!("Press {} to start", restart_key),
+            ],
+            board,
+            &draw,
+        ),
+        State::Playing => {}
+        State::Paused => show_overlay(
+            &[
+                "PAUSED".to_string(),
+                format!("Press {} to resume", pause_key),
+            ],
+            board,
+            &draw,
+        ),
+        State::ConfirmRestart { .. } => show_overlay(
+            &[
+                "Restart the game?".to_string(),
+                format!("Y / Return / {}: restart", restart_key),
+                format!("N / {}: continue", pause_key),
+            ],
+            board,
+            &draw,
+        ),
+        State::GameOver => {
+            let mut lines = model.mode.results(&model.game);
+            lines.push(format!(
+                "BEST {}",
+                model.leaderboard.mode().name().to_uppercase()
+            ));
+            for (i, entry) in model
+                .leaderboard
+                .entries()
+                .iter()
+                .take(SHOWN_ENTRIES)
+                .enumerate()
+            {
+                let marker = if model.rank == Some(i) { "> " } else { "" };
+                lines.push(format!(
+                    "{}{}. {}",
+                    marker,
+                    i + 1,
+                    entry_line(model.mode, entry)
+                ));
+            }
+            lines.push(format!(
+                "NEXT MODE {}",
+                model.next_mode.name().to_uppercase()
+            ));
+            lines.push(modes.join("  "));
+            lines.push(format!("Press {} to play again", restart_key));
+            show_overlay(&lines, board, &draw);
         }
-        if y < 0 || y > BOARD_HEIGHT as i32 - 1 {
-            return false;
+    }
+    draw.to_frame(app, &frame).unwrap();
+}
+
+/// One leaderboard entry, led by what `mode` ranks on.
+fn entry_line(mode: Mode, entry: &Entry) -> String {
+    let result = match mode {
+        Mode::Sprint => format_time(entry.time),
+        Mode::Marathon | Mode::Ultra => entry.score.to_string(),
+    };
+    return format!("{}  {}", result, format_date(entry.date));
+}
+
+fn key_names(bindings: &Bindings, action: Action) -> String {
+    let names: Vec<String> = bindings
+        .keys(action)
+        .iter()
+        .map(|k| format!("{:?}", k))
+        .collect();
+    return names.join(" / ");
+}
+
+/// Dims the board and writes `lines` centered over it.
+fn show_overlay(lines: &[String], board: &Board, draw: &Draw) {
+    let width = board.width() as f32;
+    let height = board.height() as f32;
+    let x = BLOCK_SIZE * (width - 1.0) / 2.0;
+    let y = BLOCK_SIZE * (height - 1.0) / 2.0;
+    let bg = rgba8(T_BG.red, T_BG.green, T_BG.blue, OVERLAY_ALPHA);
+    draw.rect()
+        .x_y(x, y)
+        .w(BLOCK_SIZE * width)
+        .h(BLOCK_SIZE * height)
+        .color(bg);
+    let top = y + BLOCK_SIZE * (lines.len() as f32 / 2.0);
+    for (i, line) in lines.iter().enumerate() {
+        draw.text(line)
+            .x_y(x, top - BLOCK_SIZE * i as f32)
+            .color(T_WHITE);
+    }
+}
+
+fn show_counts(score: &Score, mode: Mode, clock: Duration, board: &Board, draw: &Draw) {
+    let mut lines = vec![
+        mode.name().to_uppercase(),
+        format!("TIME {}", format_time(clock)),
+        format!("SCORE {}", score.points()),
+        format!("LEVEL {}", score.level()),
+        format!("LINES {}", score.lines()),
+    ];
+    if let Some(combo) = score.combo() {
+        if combo > 0 {
+            lines.push(format!("COMBO {}", combo));
         }
-        if self.blocks[y as usize][x as usize] > 0 {
-            return false;
+    }
+    if score.back_to_back() {
+        lines.push("BACK-TO-BACK".to_string());
+    }
+    if let Some(clear) = score.last_clear() {
+        lines.push(clear.name());
+        if clear.perfect {
+            lines.push("PERFECT CLEAR".to_string());
         }
-        return true;
     }
+    show_lines(&lines, board, draw);
+}
 
-    fn get_blocks(&self) -> Vec<Block> {
-        let mut blocks: Vec<Block> = vec![];
-        for x in (0..BOARD_WIDTH).into_iter() {
-            for y in (0..BOARD_HEIGHT_PLAYABLE).into_iter() {
-                match self.blocks[y][x] {
-                    0 => blocks.push(Block::new(x as i32, y as i32, T_WHITE)),
-                    1 => blocks.push(Block::new(x as i32, y as i32, C_LOCATED)),
-                    _ => (),
-                }
-            }
+/// Writes `lines` one per row left of the board, below the hold mino.
+fn show_lines(lines: &[String], board: &Board, draw: &Draw) {
+    for (i, line) in lines.iter().enumerate() {
+        let y = BLOCK_SIZE * (board.height() as i32 - 5 - i as i32) as f32;
+        draw.text(line).x_y(BLOCK_SIZE * -5.0, y).color(C_STR);
+    }
+}
+
+fn show_hold(hold: Option<&Mino>, can_hold: bool, board: &Board, draw: &Draw) {
+    let x = BLOCK_SIZE * -5.0;
+    let y = BLOCK_SIZE * board.height() as f32;
+    draw.text("HOLD").x_y(x, y).color(C_STR);
+    if let Some(mino) = hold {
+        let color = if can_hold {
+            shape_color(mino.shape())
+        } else {
+            C_LOCATED
+        };
+        // Held minos sit at the spawn position, so move them to the left of
+        // the board.
+        let (spawn_x, _) = board.spawn_position();
+        for b in mino.get_blocks() {
+            draw_block(
+                &Block {
+                    x: b.x - spawn_x - 5,
+                    y: b.y - 3,
+                },
+                color,
+                draw,
+            );
         }
-        return blocks;
     }
+}
 
-    fn put_mino(&mut self, mino: &Mino) {
+fn show_next(minos: &[&Mino], board: &Board, draw: &Draw) {
+    let next_x = board.width() as i32 + 3;
+    let x = BLOCK_SIZE * next_x as f32;
+    let y = BLOCK_SIZE * board.height() as f32;
+    draw.text("NEXT").x_y(x, y).color(C_STR);
+    // Queued minos sit at the spawn position, so stack them to the right of
+    // the board.
+    let (spawn_x, _) = board.spawn_position();
+    for (i, mino) in minos.iter().enumerate() {
+        let color = shape_color(mino.shape());
+        let shift_y = -3 - 3 * i as i32;
         for b in mino.get_blocks() {
-            self.put_block(&b);
+            draw_block(
+                &Block {
+                    x: b.x - spawn_x + next_x,
+                    y: b.y + shift_y,
+                },
+                color,
+                draw,
+            );
         }
     }
+}
+
+fn shape_color(shape: Shape) -> Rgb8 {
+    match shape {
+        Shape::T => C_TMINO,
+        Shape::S => C_SMINO,
+        Shape::Z => C_ZMINO,
+        Shape::O => C_OMINO,
+        Shape::J => C_JMINO,
+        Shape::L => C_LMINO,
+        Shape::I => C_IMINO,
+    }
+}
+
+/// A `Draw` placing cell `(0, 0)` of the board at the origin, scaled so the
+/// board and the panels beside it fit the window.
+fn board_draw(app: &App, dimensions: Dimensions) -> Draw {
+    return fit_board(app.draw(), app.window_rect(), dimensions);
+}
+
+/// Like `board_draw`, but fitting the board and its panels into `area` of
+/// the window, with `draw` drawing on the whole window.
+fn fit_board(draw: Draw, area: Rect, dimensions: Dimensions) -> Draw {
+    let width = dimensions.width as f32;
+    let height = dimensions.height as f32;
+    // Rows used, counting the REPLAY label below the board and the counts,
+    // which may reach further down on short boards.
+    let top = height + 1.0;
+    let bottom = (-2.0f32).min(height - COUNTS_ROWS as f32);
+    let left = -0.5 - LEFT_PANEL;
+    let right = width - 0.5 + RIGHT_PANEL;
+    let scale =
+        (area.w() / (BLOCK_SIZE * (right - left))).min(area.h() / (BLOCK_SIZE * (top - bottom)));
+    return draw
+        .x_y(
+            (area.left() + area.right()) / 2.0,
+            (area.top() + area.bottom()) / 2.0,
+        )
+        .scale(scale)
+        .x_y(
+            -BLOCK_SIZE * (left + right) / 2.0,
+            -BLOCK_SIZE * (top + bottom) / 2.0,
+        );
+}
+
+fn block_location(block: &Block) -> (f32, f32) {
+    let loc_x = BLOCK_SIZE * block.x as f32;
+    let loc_y = BLOCK_SIZE * block.y as f32;
+    return (loc_x, loc_y);
+}
+
+fn draw_block(block: &Block, color: impl IntoLinSrgba<f32>, draw: &Draw) {
+    draw_squashed_block(block, color, 1.0, draw);
+}
+
+/// Draws `block` at `height` times its full height, centred on its cell.
+fn draw_squashed_block(block: &Block, color: impl IntoLinSrgba<f32>, height: f32, draw: &Draw) {
+    let (loc_x, loc_y) = block_location(block);
+    let block_size = BLOCK_SIZE * 0.95;
+    draw.rect()
+        .x_y(loc_x, loc_y)
+        .w(block_size)
+        .h(block_size * height)
+        .color(color);
+}
+
+fn draw_ghost(mino: &Mino, draw: &Draw) {
+    let color = shape_color(mino.shape());
+    let ghost_color = rgba8(color.red, color.green, color.blue, GHOST_ALPHA);
+    for b in mino.get_blocks() {
+        draw_block(&b, ghost_color, draw);
+    }
+}
 
-    fn put_block(&mut self, block: &Block) {
-        let x = block.x as usize;
-        let y = block.y as usize;
-        self.blocks[y][x] = 1;
+fn draw_mino(mino: &Mino, draw: &Draw) {
+    let color = shape_color(mino.shape());
+    for b in mino.get_blocks() {
+        draw_block(&b, color, draw);
     }
+}
 
-    fn delete_line(&mut self) -> u32 {
-        let mut delete_count = 0;
-        for y in (0..(BOARD_HEIGHT - 1)).into_iter().rev() {
-            let line = self.blocks[y];
-            if line.iter().all(|&b| b == 1) {
-                delete_count += 1;
-                for yy in (y..(BOARD_HEIGHT - 2)).into_iter() {
-                    self.blocks[yy] = self.blocks[yy + 1];
+fn draw_board(board: &Board, phase: &Phase, delays: Delays, draw: &Draw) {
+    let clearing = match phase {
+        Phase::LineClear { rows, elapsed } => Some((rows, progress(*elapsed, delays.line_clear))),
+        _ => None,
+    };
+    for x in (0..board.width() as i32).into_iter() {
+        for y in (0..board.height() as i32).into_iter() {
+            let color = match board.cell_at(x, y) {
+                Some(Cell::Filled(shape)) => shape_color(shape),
+                Some(Cell::Garbage) => C_GARBAGE,
+                _ => T_WHITE,
+            };
+            let block = Block { x: x, y: y };
+            match clearing {
+                Some((rows, t)) if rows.contains(&(y as usize)) => {
+                    draw_clearing_block(&block, color, t, draw)
                 }
-                self.blocks[BOARD_HEIGHT - 1] = [0; BOARD_WIDTH];
+                _ => draw_block(&block, color, draw),
             }
         }
-        return delete_count;
     }
+}
 
-    fn draw(&self, draw: &Draw) {
-        let blocks: Vec<Block> = self.get_blocks();
-        for b in blocks {
-            b.draw(&draw);
-        }
+/// How far `elapsed` is through `total`, from 0 to 1.
+fn progress(elapsed: Duration, total: Duration) -> f32 {
+    if total == Duration::from_secs(0) {
+        return 1.0;
+    }
+    return (elapsed.as_secs_f32() / total.as_secs_f32()).min(1.0);
+}
+
+/// Draws a block of a row being cleared, `t` of the way through the
+/// line-clear delay: it flashes for the first half, then collapses.
+fn draw_clearing_block(block: &Block, color: Rgb8, t: f32, draw: &Draw) {
+    if t < 0.5 {
+        let color = if (t * 8.0) as i32 % 2 == 0 {
+            WHITE
+        } else {
+            color
+        };
+        draw_block(block, color, draw);
+    } else {
+        draw_squashed_block(block, color, (1.0 - t) * 2.0, draw);
+    }
+}
+
+fn draw_lock_flash(flash: &LockFlash, draw: &Draw) {
+    let t = progress(flash.elapsed, LOCK_FLASH_TIME);
+    let alpha = (FLASH_ALPHA as f32 * (1.0 - t)) as u8;
+    let flash_color = rgba8(WHITE.red, WHITE.green, WHITE.blue, alpha);
+    for b in flash.blocks.iter() {
+        draw_block(b, flash_color, draw);
     }
 }
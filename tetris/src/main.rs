@@ -1,11 +1,30 @@
 use nannou;
 
+use tetris::challenge;
 use tetris::event;
 use tetris::model;
 use tetris::update;
+use tetris::versus;
 use tetris::view;
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--puzzles") {
+        nannou::app(challenge::model)
+            .event(challenge::event)
+            .update(challenge::update)
+            .simple_window(challenge::view)
+            .run();
+        return;
+    }
+    let versus_flags = ["--versus", "--host", "--join"];
+    if std::env::args().any(|arg| versus_flags.contains(&arg.as_str())) {
+        nannou::app(versus::model)
+            .event(versus::event)
+            .update(versus::update)
+            .simple_window(versus::view)
+            .run();
+        return;
+    }
     nannou::app(model)
         .event(event)
         .update(update)
@@ -0,0 +1,110 @@
+//! Game modes: what the player is playing for and when a game ends.
+//!
+//! A topped-out game always ends; Sprint and Ultra also end once their goal
+//! is reached, which front-ends check with `Mode::is_complete` after every
+//! step.
+
+use std::time::Duration;
+
+use crate::engine::Game;
+
+/// Lines to clear in Sprint.
+pub const SPRINT_LINES: u32 = 40;
+/// Time limit of Ultra.
+pub const ULTRA_TIME: Duration = Duration::from_secs(120);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    /// Endless play, speeding up every 10 lines.
+    Marathon,
+    /// Clear `SPRINT_LINES` lines as fast as possible.
+    Sprint,
+    /// Score as much as possible within `ULTRA_TIME`.
+    Ultra,
+}
+
+pub const MODES: [Mode; 3] = [Mode::Marathon, Mode::Sprint, Mode::Ultra];
+
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Marathon => "marathon",
+            Mode::Sprint => "sprint",
+            Mode::Ultra => "ultra",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Mode> {
+        return MODES.iter().copied().find(|m| m.name() == name);
+    }
+
+    /// Whether `game` reached the goal of this mode.
+    pub fn is_complete(&self, game: &Game) -> bool {
+        match self {
+            Mode::Marathon => false,
+            Mode::Sprint => game.score().lines() >= SPRINT_LINES,
+            Mode::Ultra => game.elapsed() >= ULTRA_TIME,
+        }
+    }
+
+    /// Whether `game` is over, either topped out or complete.
+    pub fn is_over(&self, game: &Game) -> bool {
+        return !game.is_active() || self.is_complete(game);
+    }
+
+    /// The time to show while playing: time left in Ultra, time played
+    /// otherwise.
+    pub fn clock(&self, game: &Game) -> Duration {
+        match self {
+            Mode::Ultra => ULTRA_TIME
+                .checked_sub(game.elapsed())
+                .unwrap_or(Duration::from_secs(0)),
+            _ => game.elapsed(),
+        }
+    }
+
+    /// A summary of a finished game, headline first.
+    pub fn results(&self, game: &Game) -> Vec<String> {
+        let score = game.score();
+        let time = format_time(game.elapsed());
+        match self {
+            Mode::Sprint if self.is_complete(game) => vec![
+                "SPRINT COMPLETE".to_string(),
+                format!("TIME {}", time),
+                format!("SCORE {}", score.points()),
+            ],
+            Mode::Sprint => vec![
+                "GAME OVER".to_string(),
+                format!("LINES {}/{}", score.lines(), SPRINT_LINES),
+                format!("TIME {}", time),
+            ],
+            Mode::Ultra => vec![
+                if self.is_complete(game) {
+                    "TIME UP".to_string()
+                } else {
+                    "GAME OVER".to_string()
+                },
+                format!("SCORE {}", score.points()),
+                format!("LINES {}", score.lines()),
+            ],
+            Mode::Marathon => vec![
+                "GAME OVER".to_string(),
+                format!("SCORE {}", score.points()),
+                format!("LEVEL {}", score.level()),
+                format!("LINES {}", score.lines()),
+                format!("TIME {}", time),
+            ],
+        }
+    }
+}
+
+/// Formats `time` as `m:ss.cc`.
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    return format!(
+        "{}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    );
+}
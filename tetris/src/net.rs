@@ -0,0 +1,443 @@
+//! Versus across machines: the messages two peers exchange over TCP, one per
+//! line, e.g.
+//!
+//! ```text
+//! hello 3
+//! start bag7 1234 10 20 4 100 300 off
+//! garbage 3
+//! board 2 14 9 ..T......./.TTT....../...
+//! over
+//! ```
+//!
+//! Both peers send `hello` with the protocol version on connecting. The host
+//! starts every match with `start`, so both games deal the same minos on the
+//! same board with the same delays and all-spin rule. Then each peer sends
+//! the garbage it attacks with, a snapshot of its board whenever that
+//! changes, and `over` once it tops out.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::engine::{Board, Cell, Delays, Dimensions, Game, MIN_HIDDEN_ROWS};
+use crate::randomizer::RandomizerKind;
+
+/// Bumped whenever messages change, so mismatched builds refuse to play.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// What the opponent sees of a game: the visible board with the falling mino
+/// drawn in, and the counts next to it.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub board: Board,
+    /// Garbage queued against the player and not yet risen.
+    pub pending: u32,
+    pub sent: u32,
+    pub lines: u32,
+}
+
+impl Snapshot {
+    pub fn of_game(game: &Game) -> Self {
+        let board = game.board();
+        let mut rows: Vec<Vec<Cell>> = (0..board.height() as i32)
+            .into_iter()
+            .map(|y| {
+                (0..board.width() as i32)
+                    .into_iter()
+                    .map(|x| board.cell_at(x, y).unwrap())
+                    .collect()
+            })
+            .collect();
+        if game.is_active() && game.is_falling() {
+            for b in game.mino().get_blocks() {
+                if let Some(row) = rows.get_mut(b.y as usize) {
+                    row[b.x as usize] = Cell::Filled(game.mino().shape());
+                }
+            }
+        }
+        Snapshot {
+            board: Board::from_rows(board.dimensions(), &rows),
+            pending: game.pending_garbage(),
+            sent: game.sent(),
+            lines: game.score().lines(),
+        }
+    }
+
+    /// The visible rows, top row first, separated by `/`.
+    fn rows_text(&self) -> String {
+        let board = &self.board;
+        let rows: Vec<String> = (0..board.height() as i32)
+            .into_iter()
+            .rev()
+            .map(|y| {
+                (0..board.width() as i32)
+                    .into_iter()
+                    .map(|x| board.cell_at(x, y).unwrap().to_char())
+                    .collect()
+            })
+            .collect();
+        return rows.join("/");
+    }
+
+    fn parse_rows(text: &str) -> Result<Board, String> {
+        let mut rows = vec![];
+        for row_text in text.split('/').rev() {
+            let row: Vec<Cell> = row_text
+                .chars()
+                .map(Cell::from_char)
+                .collect::<Option<_>>()
+                .ok_or_else(|| format!("invalid board row `{}`", row_text))?;
+            rows.push(row);
+        }
+        let dimensions = Dimensions {
+            width: rows[0].len(),
+            height: rows.len(),
+            hidden_rows: MIN_HIDDEN_ROWS,
+        };
+        dimensions.validate().map_err(|errors| errors.join("; "))?;
+        if rows.iter().any(|row| row.len() != dimensions.width) {
+            return Err("board rows differ in width".to_string());
+        }
+        return Ok(Board::from_rows(dimensions, &rows));
+    }
+}
+
+#[derive(Clone)]
+pub enum Message {
+    Hello {
+        version: u32,
+    },
+    /// Sent by the host to start a match.
+    Start {
+        kind: RandomizerKind,
+        seed: u64,
+        dimensions: Dimensions,
+        delays: Delays,
+        all_spin: bool,
+    },
+    Garbage {
+        lines: u32,
+    },
+    Board(Snapshot),
+    /// The sender topped out.
+    Over,
+}
+
+impl Message {
+    pub fn to_line(&self) -> String {
+        match self {
+            Message::Hello { version } => format!("hello {}", version),
+            Message::Start {
+                kind,
+                seed,
+                dimensions,
+                delays,
+                all_spin,
+            } => format!(
+                "start {} {} {} {} {} {} {} {}",
+                kind.name(),
+                seed,
+                dimensions.width,
+                dimensions.height,
+                dimensions.hidden_rows,
+                delays.are.as_millis(),
+                delays.line_clear.as_millis(),
+                if *all_spin { "on" } else { "off" }
+            ),
+            Message::Garbage { lines } => format!("garbage {}", lines),
+            Message::Board(snapshot) => format!(
+                "board {} {} {} {}",
+                snapshot.pending,
+                snapshot.sent,
+                snapshot.lines,
+                snapshot.rows_text()
+            ),
+            Message::Over => "over".to_string(),
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Message, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |i: usize| -> Result<u64, String> {
+            return words
+                .get(i)
+                .and_then(|w| w.parse().ok())
+                .ok_or_else(|| format!("invalid message `{}`", line));
+        };
+        let count = |i: usize| -> Result<u32, String> {
+            return words
+                .get(i)
+                .and_then(|w| w.parse().ok())
+                .ok_or_else(|| format!("invalid message `{}`", line));
+        };
+        let message = match words.first() {
+            Some(&"hello") if words.len() == 2 => Message::Hello { version: count(1)? },
+            Some(&"start") if words.len() == 9 => {
+                let kind = RandomizerKind::from_name(words[1])
+                    .ok_or_else(|| format!("unknown randomizer `{}`", words[1]))?;
+                let dimensions = Dimensions {
+                    width: number(3)? as usize,
+                    height: number(4)? as usize,
+                    hidden_rows: number(5)? as usize,
+                };
+                dimensions.validate().map_err(|errors| errors.join("; "))?;
+                let delays = Delays {
+                    are: Duration::from_millis(number(6)?),
+                    line_clear: Duration::from_millis(number(7)?),
+                };
+                let all_spin = match words[8] {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("invalid message `{}`", line)),
+                };
+                Message::Start {
+                    kind: kind,
+                    seed: number(2)?,
+                    dimensions: dimensions,
+                    delays: delays,
+                    all_spin: all_spin,
+                }
+            }
+            Some(&"garbage") if words.len() == 2 => Message::Garbage { lines: count(1)? },
+            Some(&"board") if words.len() == 5 => Message::Board(Snapshot {
+                board: Snapshot::parse_rows(words[4])?,
+                pending: count(1)?,
+                sent: count(2)?,
+                lines: count(3)?,
+            }),
+            Some(&"over") if words.len() == 1 => Message::Over,
+            _ => return Err(format!("invalid message `{}`", line)),
+        };
+        return Ok(message);
+    }
+}
+
+/// A connection to the other player. Neither sending nor receiving blocks,
+/// so it can be polled every frame.
+pub struct Connection {
+    stream: TcpStream,
+    /// Bytes received after the last complete line.
+    buffer: Vec<u8>,
+    /// Bytes sent but not yet taken by the socket.
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    /// Waits on `addr` for the other player to join.
+    pub fn host<A: ToSocketAddrs>(addr: A) -> io::Result<Connection> {
+        let listener = TcpListener::bind(addr)?;
+        return Connection::accept(&listener);
+    }
+
+    /// Waits for the other player to join on `listener`.
+    pub fn accept(listener: &TcpListener) -> io::Result<Connection> {
+        let (stream, _) = listener.accept()?;
+        return Connection::handshake(stream);
+    }
+
+    pub fn join<A: ToSocketAddrs>(addr: A) -> io::Result<Connection> {
+        return Connection::handshake(TcpStream::connect(addr)?);
+    }
+
+    /// Exchanges `hello`s, checking both peers speak the same protocol.
+    fn handshake(stream: TcpStream) -> io::Result<Connection> {
+        stream.set_nodelay(true)?;
+        let mut connection = Connection {
+            stream: stream,
+            buffer: vec![],
+            outgoing: vec![],
+        };
+        connection.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+        })?;
+        while !connection.buffer.contains(&b'\n') {
+            connection.read_available()?;
+        }
+        // Only the first line is the opponent's hello; what follows it is
+        // left for `receive`.
+        let end = connection.buffer.iter().position(|&b| b == b'\n').unwrap();
+        let line: Vec<u8> = connection.buffer.drain(..=end).collect();
+        match Message::parse(&String::from_utf8_lossy(&line)) {
+            Ok(Message::Hello { version }) if version == PROTOCOL_VERSION => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("opponent does not speak protocol {}", PROTOCOL_VERSION),
+                ))
+            }
+        }
+        connection.stream.set_nonblocking(true)?;
+        return Ok(connection);
+    }
+
+    /// Queues `message` and writes as much of the queue as the socket takes;
+    /// the rest goes out on later sends and receives.
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        let line = message.to_line() + "\n";
+        self.outgoing.extend_from_slice(line.as_bytes());
+        return self.flush();
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(disconnected()),
+                Ok(count) => {
+                    self.outgoing.drain(..count);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        return Ok(());
+    }
+
+    /// The messages received since the last call. Fails once the other
+    /// player disconnected and everything they sent was received, or when
+    /// they send something unreadable.
+    pub fn receive(&mut self) -> io::Result<Vec<Message>> {
+        self.flush()?;
+        let mut closed = false;
+        loop {
+            match self.read_available() {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    closed = true;
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        let messages = self.take_lines()?;
+        if closed && messages.is_empty() {
+            return Err(disconnected());
+        }
+        return Ok(messages);
+    }
+
+    /// Reads what has arrived into the buffer, waiting for at least one byte
+    /// unless the stream is non-blocking.
+    fn read_available(&mut self) -> io::Result<()> {
+        let mut bytes = [0; 4096];
+        let count = self.stream.read(&mut bytes)?;
+        if count == 0 {
+            return Err(disconnected());
+        }
+        self.buffer.extend_from_slice(&bytes[..count]);
+        return Ok(());
+    }
+
+    /// Parses the complete lines in the buffer, keeping the partial one.
+    fn take_lines(&mut self) -> io::Result<Vec<Message>> {
+        let end = match self.buffer.iter().rposition(|&b| b == b'\n') {
+            Some(end) => end,
+            None => return Ok(vec![]),
+        };
+        let lines: Vec<u8> = self.buffer.drain(..=end).collect();
+        return String::from_utf8_lossy(&lines)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                Message::parse(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            })
+            .collect();
+    }
+}
+
+fn disconnected() -> io::Error {
+    return io::Error::new(io::ErrorKind::UnexpectedEof, "opponent disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Input;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Polls `connection` until it has received something.
+    fn receive_some(connection: &mut Connection) -> Vec<Message> {
+        for _ in (0..500).into_iter() {
+            let messages = connection.receive().unwrap();
+            if !messages.is_empty() {
+                return messages;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("nothing received");
+    }
+
+    #[test]
+    fn snapshot_round_trips() {
+        let mut game = Game::with_seed(RandomizerKind::Bag7, 7);
+        game.set_delays(Delays::none());
+        game.receive_garbage(2);
+        game.apply(Input::HardDrop);
+        game.receive_garbage(1);
+        let line = Message::Board(Snapshot::of_game(&game)).to_line();
+        match Message::parse(&line) {
+            Ok(Message::Board(snapshot)) => {
+                assert_eq!(snapshot.pending, 1);
+                assert_eq!(Message::Board(snapshot).to_line(), line);
+            }
+            _ => panic!("`{}` did not parse as a board", line),
+        }
+    }
+
+    #[test]
+    fn counts_must_fit() {
+        assert!(Message::parse("garbage 4294967295").is_ok());
+        assert!(Message::parse("garbage 4294967296").is_err());
+        assert!(Message::parse("garbage -1").is_err());
+        assert!(Message::parse("hello 4294967299").is_err());
+    }
+
+    #[test]
+    fn start_carries_the_delays() {
+        let line = "start bag7 1234 10 20 4 50 200 off";
+        match Message::parse(line) {
+            Ok(Message::Start { delays, .. }) => {
+                assert_eq!(delays.are, Duration::from_millis(50));
+                assert_eq!(delays.line_clear, Duration::from_millis(200));
+            }
+            _ => panic!("`{}` did not parse as a start", line),
+        }
+        assert!(Message::parse("start bag7 1234 10 20 4 off").is_err());
+    }
+
+    #[test]
+    fn peers_exchange_messages_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let joiner = thread::spawn(move || {
+            let mut connection = Connection::join(addr).unwrap();
+            let messages = receive_some(&mut connection);
+            connection.send(&Message::Garbage { lines: 4 }).unwrap();
+            return messages.iter().map(|m| m.to_line()).collect::<Vec<_>>();
+        });
+        let mut host = Connection::accept(&listener).unwrap();
+        let start = Message::Start {
+            kind: RandomizerKind::Bag7,
+            seed: 42,
+            dimensions: Dimensions::default(),
+            delays: Delays::default(),
+            all_spin: true,
+        };
+        host.send(&start).unwrap();
+        let received = receive_some(&mut host);
+        assert_eq!(joiner.join().unwrap(), vec![start.to_line()]);
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].to_line(), "garbage 4");
+        let mut disconnected = false;
+        for _ in (0..500).into_iter() {
+            if host.receive().is_err() {
+                disconnected = true;
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(disconnected);
+    }
+}
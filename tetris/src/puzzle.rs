@@ -0,0 +1,400 @@
+//! Puzzles: a starting board, a fixed list of pieces and a goal to reach
+//! with them, read from files like
+//!
+//! ```text
+//! name First T-spin double
+//! pieces T I
+//! goal tsd
+//! board
+//! ..........
+//! ###..#####
+//! ####.#####
+//! ```
+//!
+//! `goal` is `lines <n>`, `perfect-clear` or `tsd`, checked after every
+//! lock. An optional `size <width> <height> <hidden rows>` line sets a board
+//! other than the default, and the rows after `board` are read with
+//! `Board::from_text`. Lines starting with `//` before `board` are comments.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::engine::{Board, Dimensions, Game, Phase, Shape, TSpin};
+
+/// The extension of puzzle files in a puzzle directory.
+pub const EXTENSION: &str = "puzzle";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Goal {
+    Lines(u32),
+    /// Leave the board empty after clearing lines.
+    PerfectClear,
+    TSpinDouble,
+}
+
+impl Goal {
+    fn parse(words: &[&str]) -> Result<Goal, String> {
+        match words {
+            ["lines", n] => n
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .map(Goal::Lines)
+                .ok_or_else(|| format!("invalid line count `{}`", n)),
+            ["perfect-clear"] => Ok(Goal::PerfectClear),
+            ["tsd"] => Ok(Goal::TSpinDouble),
+            _ => Err(format!("unknown goal `{}`", words.join(" "))),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Goal::Lines(1) => "CLEAR 1 LINE".to_string(),
+            Goal::Lines(n) => format!("CLEAR {} LINES", n),
+            Goal::PerfectClear => "PERFECT CLEAR".to_string(),
+            Goal::TSpinDouble => "T-SPIN DOUBLE".to_string(),
+        }
+    }
+
+    /// Whether `game` has reached the goal.
+    pub fn is_met(&self, game: &Game) -> bool {
+        match self {
+            Goal::Lines(n) => game.score().lines() >= *n,
+            Goal::PerfectClear => game.score().last_clear().map_or(false, |c| c.perfect),
+            Goal::TSpinDouble => game
+                .score()
+                .last_clear()
+                .map_or(false, |c| c.lines == 2 && c.tspin == TSpin::Full),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Solved,
+    Failed,
+}
+
+#[derive(Clone)]
+pub struct Puzzle {
+    name: String,
+    pieces: Vec<Shape>,
+    goal: Goal,
+    board: Board,
+}
+
+impl Puzzle {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn goal(&self) -> Goal {
+        return self.goal;
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Puzzle, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        return Puzzle::from_text(&text);
+    }
+
+    pub fn from_text(text: &str) -> Result<Puzzle, String> {
+        let mut name = None;
+        let mut pieces = None;
+        let mut goal = None;
+        let mut dimensions = Dimensions::default();
+        let mut lines = text.lines();
+        let mut board_text = None;
+        while let Some(line) = lines.next() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.first() {
+                None => {}
+                Some(word) if word.starts_with("//") => {}
+                Some(&"name") => name = Some(words[1..].join(" ")),
+                Some(&"pieces") => {
+                    let shapes: Vec<Shape> = words[1..]
+                        .iter()
+                        .flat_map(|w| w.chars())
+                        .map(|c| {
+                            Shape::from_letter(c).ok_or_else(|| format!("unknown piece `{}`", c))
+                        })
+                        .collect::<Result<_, _>>()?;
+                    pieces = Some(shapes);
+                }
+                Some(&"goal") => goal = Some(Goal::parse(&words[1..])?),
+                Some(&"size") => dimensions = parse_size(&words[1..])?,
+                Some(&"board") => {
+                    board_text = Some(lines.collect::<Vec<&str>>().join("\n"));
+                    break;
+                }
+                Some(word) => return Err(format!("unknown line `{}`", word)),
+            }
+        }
+        let pieces = pieces.ok_or_else(|| "missing `pieces` line".to_string())?;
+        if pieces.is_empty() {
+            return Err("a puzzle needs at least one piece".to_string());
+        }
+        let board_text = board_text.ok_or_else(|| "missing `board` line".to_string())?;
+        return Ok(Puzzle {
+            name: name.unwrap_or_else(|| "Untitled".to_string()),
+            pieces: pieces,
+            goal: goal.ok_or_else(|| "missing `goal` line".to_string())?,
+            board: Board::from_text(dimensions, &board_text)?,
+        });
+    }
+
+    /// A game on the starting board dealing the puzzle's pieces.
+    pub fn new_game(&self) -> Game {
+        return Game::with_sequence(self.board.clone(), self.pieces.clone());
+    }
+
+    /// Pieces not dealt yet, besides the falling and the held one.
+    pub fn remaining(&self, game: &Game) -> usize {
+        let held = game.hold().map_or(0, |_| 1);
+        return self
+            .pieces
+            .len()
+            .saturating_sub(game.pieces() as usize + held + 1);
+    }
+
+    /// Whether `game` solved the puzzle or can no longer solve it. The last
+    /// lines cleared count once their delay is over.
+    pub fn outcome(&self, game: &Game) -> Option<Outcome> {
+        if self.goal.is_met(game) {
+            return Some(Outcome::Solved);
+        }
+        let clearing = match game.phase() {
+            Phase::LineClear { .. } => true,
+            _ => false,
+        };
+        if !game.is_active() || (!clearing && !self.has_piece_left(game)) {
+            return Some(Outcome::Failed);
+        }
+        return None;
+    }
+
+    /// Whether one of the puzzle's pieces can still be played: falling,
+    /// coming next, or held while it can be swapped in.
+    fn has_piece_left(&self, game: &Game) -> bool {
+        let left = self.pieces.len().saturating_sub(game.pieces() as usize);
+        if left == 0 {
+            return false;
+        }
+        // The next mino spawns with hold available, so a held piece can be
+        // swapped in then.
+        if !game.is_falling() || game.can_hold() {
+            return true;
+        }
+        if left > 1 {
+            return true;
+        }
+        // Held this turn with one piece left: the falling and the held mino
+        // are that piece and the first piece dealt again, in some order. The
+        // falling one is ours unless it was dealt last, i.e. has the first
+        // piece's shape while the held one does not.
+        let first = self.pieces[0];
+        let held = game.hold().map(|mino| mino.shape());
+        return game.mino().shape() != first || held == Some(first);
+    }
+}
+
+/// The dimensions on a `size <width> <height> <hidden rows>` line.
+fn parse_size(words: &[&str]) -> Result<Dimensions, String> {
+    let numbers: Vec<usize> = words
+        .iter()
+        .map(|w| w.parse().ok())
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("invalid size `{}`", words.join(" ")))?;
+    let dimensions = match numbers[..] {
+        [width, height, hidden_rows] => Dimensions {
+            width: width,
+            height: height,
+            hidden_rows: hidden_rows,
+        },
+        _ => return Err(format!("invalid size `{}`", words.join(" "))),
+    };
+    dimensions.validate().map_err(|errors| errors.join("; "))?;
+    return Ok(dimensions);
+}
+
+/// The puzzle files in `dir`, in the order to play them: by file name.
+pub fn puzzle_paths<P: AsRef<Path>>(dir: P) -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |e| e == EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    return Ok(paths);
+}
+
+/// The names of the puzzles solved so far, kept one per line in a file
+/// between sessions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Progress {
+    solved: HashSet<String>,
+}
+
+impl Progress {
+    pub fn is_solved(&self, name: &str) -> bool {
+        return self.solved.contains(name);
+    }
+
+    /// Marks `name` solved, returning false if it already was.
+    pub fn solve(&mut self, name: &str) -> bool {
+        return self.solved.insert(name.to_string());
+    }
+
+    /// Loads progress from `path`; a missing file is no progress.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Progress> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        let solved = text
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect();
+        return Ok(Progress { solved: solved });
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        let mut names: Vec<&String> = self.solved.iter().collect();
+        names.sort();
+        let text: String = names.iter().map(|n| format!("{}\n", n)).collect();
+        fs::write(&tmp, text)?;
+        return fs::rename(&tmp, path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tests::play;
+    use crate::engine::Input;
+
+    const PUZZLE: &str = "\
+// Clears two lines with one O.
+name Corner O
+pieces O
+goal lines 2
+board
+########..
+########..
+";
+
+    fn puzzle(pieces: &str, goal: &str) -> Puzzle {
+        let text = format!("pieces {}\ngoal {}\nboard\n", pieces, goal);
+        return Puzzle::from_text(&text).unwrap();
+    }
+
+    #[test]
+    fn reads_a_puzzle() {
+        let puzzle = Puzzle::from_text(PUZZLE).unwrap();
+        assert_eq!(puzzle.name(), "Corner O");
+        assert_eq!(puzzle.pieces, vec![Shape::O]);
+        assert_eq!(puzzle.goal(), Goal::Lines(2));
+        assert_eq!(puzzle.board.dimensions(), Dimensions::default());
+        assert_eq!(puzzle.board.to_text(), "########..\n########..\n");
+    }
+
+    #[test]
+    fn reads_the_size_and_defaults_the_name() {
+        let text = "size 6 10 4\npieces TI LO\ngoal tsd\nboard\n######";
+        let puzzle = Puzzle::from_text(text).unwrap();
+        assert_eq!(puzzle.name(), "Untitled");
+        assert_eq!(puzzle.pieces, vec![Shape::T, Shape::I, Shape::L, Shape::O]);
+        assert_eq!(
+            puzzle.board.dimensions(),
+            Dimensions {
+                width: 6,
+                height: 10,
+                hidden_rows: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn parses_goals() {
+        assert_eq!(Goal::parse(&["lines", "3"]), Ok(Goal::Lines(3)));
+        assert_eq!(Goal::parse(&["perfect-clear"]), Ok(Goal::PerfectClear));
+        assert_eq!(Goal::parse(&["tsd"]), Ok(Goal::TSpinDouble));
+        assert!(Goal::parse(&["lines", "0"]).is_err());
+        assert!(Goal::parse(&["lines", "many"]).is_err());
+        assert!(Goal::parse(&["lines"]).is_err());
+        assert!(Goal::parse(&["win"]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_puzzles() {
+        let bad = [
+            "pieces T\ngoal win\nboard\n",
+            "pieces T\ngoal lines 0\nboard\n",
+            "size 10 20\npieces T\ngoal tsd\nboard\n",
+            "size 10 twenty 4\npieces T\ngoal tsd\nboard\n",
+            "size 2 20 4\npieces T\ngoal tsd\nboard\n",
+            "pieces TX\ngoal tsd\nboard\n",
+            "pieces\ngoal tsd\nboard\n",
+            "goal tsd\nboard\n",
+            "pieces T\nboard\n",
+            "pieces T\ngoal tsd\n",
+            "pieces T\ngoal tsd\nlevel 3\nboard\n",
+            "pieces T\ngoal tsd\nboard\n###",
+        ];
+        for text in bad.iter() {
+            assert!(Puzzle::from_text(text).is_err(), "accepted {:?}", text);
+        }
+    }
+
+    #[test]
+    fn solves_once_the_goal_is_met() {
+        let puzzle = Puzzle::from_text(PUZZLE).unwrap();
+        let board = puzzle.board.to_text();
+        let game = play(&board, &puzzle.pieces, false, &[]);
+        assert_eq!(puzzle.outcome(&game), None);
+        let mut inputs = vec![Input::MoveRight; 5];
+        inputs.push(Input::HardDrop);
+        let game = play(&board, &puzzle.pieces, false, &inputs);
+        assert_eq!(puzzle.outcome(&game), Some(Outcome::Solved));
+    }
+
+    #[test]
+    fn fails_once_the_last_piece_locks() {
+        let puzzle = puzzle("T I", "lines 1");
+        let mut game = play("", &puzzle.pieces, false, &[Input::HardDrop]);
+        assert_eq!(puzzle.outcome(&game), None);
+        game.apply(Input::HardDrop);
+        assert_eq!(puzzle.outcome(&game), Some(Outcome::Failed));
+    }
+
+    #[test]
+    fn a_held_piece_can_still_be_played() {
+        let puzzle = puzzle("T I", "lines 1");
+        let mut game = play("", &puzzle.pieces, false, &[Input::Hold, Input::HardDrop]);
+        // The T dealt again is falling, and the held T can be swapped in.
+        assert_eq!(puzzle.outcome(&game), None);
+        game.apply(Input::Hold);
+        assert_eq!(game.mino().shape(), Shape::T);
+        assert_eq!(puzzle.outcome(&game), None);
+        game.apply(Input::HardDrop);
+        assert_eq!(puzzle.outcome(&game), Some(Outcome::Failed));
+    }
+
+    #[test]
+    fn holding_the_last_piece_fails() {
+        let puzzle = puzzle("T I", "lines 1");
+        let mut game = play("", &puzzle.pieces, false, &[Input::HardDrop]);
+        // Holding the I brings up the T dealt again, which cannot be swapped
+        // back until it locks.
+        game.apply(Input::Hold);
+        assert_eq!(puzzle.outcome(&game), Some(Outcome::Failed));
+    }
+}
@@ -0,0 +1,243 @@
+//! Strategies deciding the order in which shapes are dealt.
+//!
+//! Every randomizer is built from a `u64` seed, so the same seed always deals
+//! the same sequence and a game can be replayed.
+
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::engine::{Shape, SHAPES};
+
+pub trait Randomizer {
+    fn next_shape(&mut self) -> Shape;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RandomizerKind {
+    Bag7,
+    Pure,
+    Bag14,
+    Tgm,
+}
+
+impl RandomizerKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RandomizerKind::Bag7 => "bag7",
+            RandomizerKind::Pure => "pure",
+            RandomizerKind::Bag14 => "bag14",
+            RandomizerKind::Tgm => "tgm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RandomizerKind> {
+        match name {
+            "bag7" => Some(RandomizerKind::Bag7),
+            "pure" => Some(RandomizerKind::Pure),
+            "bag14" => Some(RandomizerKind::Bag14),
+            "tgm" => Some(RandomizerKind::Tgm),
+            _ => None,
+        }
+    }
+
+    pub fn build(&self, seed: u64) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::Bag7 => Box::new(Bag::new(seed, 1)),
+            RandomizerKind::Pure => Box::new(Pure::new(seed)),
+            RandomizerKind::Bag14 => Box::new(Bag::new(seed, 2)),
+            RandomizerKind::Tgm => Box::new(Tgm::new(seed)),
+        }
+    }
+}
+
+/// Deals shuffled bags holding every shape `copies` times.
+pub struct Bag {
+    rng: StdRng,
+    copies: usize,
+    bag: Vec<Shape>,
+}
+
+impl Bag {
+    pub fn new(seed: u64, copies: usize) -> Self {
+        Bag {
+            rng: StdRng::seed_from_u64(seed),
+            copies: copies,
+            bag: vec![],
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next_shape(&mut self) -> Shape {
+        if self.bag.is_empty() {
+            for _ in (0..self.copies).into_iter() {
+                self.bag.extend(SHAPES.iter());
+            }
+            self.bag.shuffle(&mut self.rng);
+        }
+        return self.bag.pop().unwrap();
+    }
+}
+
+/// Deals a fixed list of shapes in order, starting over once through, for
+/// puzzles. Not seeded, so games using it cannot be replayed.
+pub struct Sequence {
+    shapes: Vec<Shape>,
+    next: usize,
+}
+
+impl Sequence {
+    /// `shapes` must not be empty.
+    pub fn new(shapes: Vec<Shape>) -> Self {
+        Sequence {
+            shapes: shapes,
+            next: 0,
+        }
+    }
+}
+
+impl Randomizer for Sequence {
+    fn next_shape(&mut self) -> Shape {
+        let shape = self.shapes[self.next];
+        self.next = (self.next + 1) % self.shapes.len();
+        return shape;
+    }
+}
+
+/// Deals every shape with equal probability, independent of the past.
+pub struct Pure {
+    rng: StdRng,
+}
+
+impl Pure {
+    pub fn new(seed: u64) -> Self {
+        Pure {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Randomizer for Pure {
+    fn next_shape(&mut self) -> Shape {
+        return *SHAPES.choose(&mut self.rng).unwrap();
+    }
+}
+
+const TGM_HISTORY: usize = 4;
+const TGM_ROLLS: usize = 6;
+
+/// The TGM2 randomizer: rerolls up to `TGM_ROLLS` times to avoid the last
+/// `TGM_HISTORY` shapes, and never starts with S, Z or O.
+pub struct Tgm {
+    rng: StdRng,
+    history: VecDeque<Shape>,
+    first: bool,
+}
+
+impl Tgm {
+    pub fn new(seed: u64) -> Self {
+        let history = vec![Shape::Z, Shape::S, Shape::S, Shape::Z];
+        Tgm {
+            rng: StdRng::seed_from_u64(seed),
+            history: history.into_iter().collect(),
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for Tgm {
+    fn next_shape(&mut self) -> Shape {
+        let shape = if self.first {
+            self.first = false;
+            *[Shape::I, Shape::J, Shape::L, Shape::T]
+                .choose(&mut self.rng)
+                .unwrap()
+        } else {
+            let mut shape = *SHAPES.choose(&mut self.rng).unwrap();
+            for _ in (1..TGM_ROLLS).into_iter() {
+                if !self.history.contains(&shape) {
+                    break;
+                }
+                shape = *SHAPES.choose(&mut self.rng).unwrap();
+            }
+            shape
+        };
+        self.history.pop_front();
+        self.history.push_back(shape);
+        debug_assert_eq!(self.history.len(), TGM_HISTORY);
+        return shape;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [RandomizerKind; 4] = [
+        RandomizerKind::Bag7,
+        RandomizerKind::Pure,
+        RandomizerKind::Bag14,
+        RandomizerKind::Tgm,
+    ];
+
+    fn deal(kind: RandomizerKind, seed: u64, count: usize) -> Vec<Shape> {
+        let mut randomizer = kind.build(seed);
+        return (0..count)
+            .into_iter()
+            .map(|_| randomizer.next_shape())
+            .collect();
+    }
+
+    /// How often each shape occurs in `shapes`, in the order of `SHAPES`.
+    fn counts(shapes: &[Shape]) -> Vec<usize> {
+        return SHAPES
+            .iter()
+            .map(|s| shapes.iter().filter(|&t| t == s).count())
+            .collect();
+    }
+
+    #[test]
+    fn same_seed_deals_the_same_sequence() {
+        for &kind in KINDS.iter() {
+            assert_eq!(deal(kind, 42, 100), deal(kind, 42, 100));
+            assert_ne!(deal(kind, 42, 100), deal(kind, 43, 100));
+        }
+    }
+
+    #[test]
+    fn bag7_deals_every_shape_once_per_bag() {
+        for seed in (0..20).into_iter() {
+            for bag in deal(RandomizerKind::Bag7, seed, 70).chunks(7) {
+                assert_eq!(counts(bag), vec![1; SHAPES.len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn bag14_deals_every_shape_twice_per_bag() {
+        for seed in (0..20).into_iter() {
+            for bag in deal(RandomizerKind::Bag14, seed, 140).chunks(14) {
+                assert_eq!(counts(bag), vec![2; SHAPES.len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn tgm_never_starts_with_s_z_or_o() {
+        for seed in (0..50).into_iter() {
+            let first = deal(RandomizerKind::Tgm, seed, 1)[0];
+            assert!(![Shape::S, Shape::Z, Shape::O].contains(&first));
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for &kind in KINDS.iter() {
+            assert_eq!(RandomizerKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(RandomizerKind::from_name("bag8"), None);
+    }
+}
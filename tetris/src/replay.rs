@@ -0,0 +1,354 @@
+//! Recording of a game as its seed plus the inputs applied at each frame, so
+//! it can be played back exactly.
+//!
+//! A replay only reproduces a game when the game is stepped in fixed
+//! `FRAME_TIME` steps and every input is applied before the step of the frame
+//! it was recorded at.
+//!
+//! Replays are saved as text: `randomizer`, `seed`, `mode`, `board`,
+//! `delays` and `all_spin` lines followed by one `<frame> <input>` line per
+//! input, e.g.
+//!
+//! ```text
+//! randomizer bag7
+//! seed 1234
+//! mode sprint
+//! board 10 20 4
+//! delays 100 300
+//! all_spin off
+//! 812 hard_drop
+//! ```
+//!
+//! The `board` line holds the width, visible height and hidden rows, and the
+//! `delays` line the entry and line-clear delays in milliseconds. Replays
+//! saved without `mode`, `board`, `delays` or `all_spin` lines play back as
+//! marathon on the standard board without delays or all-spins.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::engine::{Delays, Dimensions, Game, Input};
+use crate::mode::Mode;
+use crate::randomizer::RandomizerKind;
+
+pub const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReplayEvent {
+    pub frame: u64,
+    pub input: Input,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    kind: RandomizerKind,
+    seed: u64,
+    mode: Mode,
+    dimensions: Dimensions,
+    delays: Delays,
+    all_spin: bool,
+    events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    /// Starts an empty recording of `game`, played in `mode`.
+    pub fn for_game(game: &Game, mode: Mode) -> Self {
+        Replay {
+            kind: game.randomizer_kind(),
+            seed: game.seed(),
+            mode: mode,
+            dimensions: game.board().dimensions(),
+            delays: game.delays(),
+            all_spin: game.all_spin(),
+            events: vec![],
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    pub fn mode(&self) -> Mode {
+        return self.mode;
+    }
+
+    pub fn events(&self) -> &[ReplayEvent] {
+        return &self.events;
+    }
+
+    /// A fresh game dealing the same pieces as the recorded one.
+    pub fn new_game(&self) -> Game {
+        let mut game = Game::with_dimensions(self.dimensions, self.kind, self.seed);
+        game.set_delays(self.delays);
+        game.set_all_spin(self.all_spin);
+        return game;
+    }
+
+    pub fn record(&mut self, frame: u64, input: Input) {
+        self.events.push(ReplayEvent {
+            frame: frame,
+            input: input,
+        });
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        return fs::write(path, self.to_text());
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        let text = fs::read_to_string(path)?;
+        return Replay::from_text(&text)
+            .map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, msg));
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!(
+            "randomizer {}\nseed {}\nmode {}\nboard {} {} {}\ndelays {} {}\nall_spin {}\n",
+            self.kind.name(),
+            self.seed,
+            self.mode.name(),
+            self.dimensions.width,
+            self.dimensions.height,
+            self.dimensions.hidden_rows,
+            self.delays.are.as_millis(),
+            self.delays.line_clear.as_millis(),
+            if self.all_spin { "on" } else { "off" }
+        );
+        for event in self.events.iter() {
+            text += &format!("{} {}\n", event.frame, event.input.name());
+        }
+        return text;
+    }
+
+    fn from_text(text: &str) -> Result<Replay, String> {
+        let mut lines = text.lines().filter(|l| !l.trim().is_empty()).peekable();
+        let kind_name = header(lines.next(), "randomizer")?;
+        let kind = RandomizerKind::from_name(kind_name)
+            .ok_or_else(|| format!("unknown randomizer `{}`", kind_name))?;
+        let seed = header(lines.next(), "seed")?
+            .parse()
+            .map_err(|_| "invalid seed".to_string())?;
+        let mode = match lines.peek() {
+            Some(line) if line.starts_with("mode") => {
+                let mode_name = header(lines.next(), "mode")?;
+                Mode::from_name(mode_name).ok_or_else(|| format!("unknown mode `{}`", mode_name))?
+            }
+            _ => Mode::Marathon,
+        };
+        let dimensions = match lines.peek() {
+            Some(line) if line.starts_with("board") => parse_board(lines.next().unwrap())?,
+            _ => Dimensions::default(),
+        };
+        let delays = match lines.peek() {
+            Some(line) if line.starts_with("delays") => parse_delays(lines.next().unwrap())?,
+            _ => Delays::none(),
+        };
+        let all_spin = match lines.peek() {
+            Some(line) if line.starts_with("all_spin") => match header(lines.next(), "all_spin")? {
+                "on" => true,
+                "off" => false,
+                value => return Err(format!("invalid all_spin `{}`", value)),
+            },
+            _ => false,
+        };
+        let mut events = vec![];
+        for line in lines {
+            let mut words = line.split_whitespace();
+            let frame = words
+                .next()
+                .and_then(|w| w.parse().ok())
+                .ok_or_else(|| format!("invalid frame in `{}`", line))?;
+            let input = words
+                .next()
+                .and_then(Input::from_name)
+                .ok_or_else(|| format!("invalid input in `{}`", line))?;
+            events.push(ReplayEvent {
+                frame: frame,
+                input: input,
+            });
+        }
+        return Ok(Replay {
+            kind: kind,
+            seed: seed,
+            mode: mode,
+            dimensions: dimensions,
+            delays: delays,
+            all_spin: all_spin,
+            events: events,
+        });
+    }
+}
+
+/// The value of a `<key> <value>` header line.
+fn header<'a>(line: Option<&'a str>, key: &str) -> Result<&'a str, String> {
+    let line = line.ok_or_else(|| format!("missing `{}` line", key))?;
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some(k), Some(value)) if k == key => Ok(value),
+        _ => Err(format!("expected `{} <value>`, found `{}`", key, line)),
+    }
+}
+
+/// The dimensions on a `board <width> <height> <hidden rows>` line.
+fn parse_board(line: &str) -> Result<Dimensions, String> {
+    let numbers: Vec<usize> = line
+        .split_whitespace()
+        .skip(1)
+        .map(|w| w.parse().ok())
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("invalid board in `{}`", line))?;
+    let dimensions = match numbers[..] {
+        [width, height, hidden_rows] => Dimensions {
+            width: width,
+            height: height,
+            hidden_rows: hidden_rows,
+        },
+        _ => return Err(format!("invalid board in `{}`", line)),
+    };
+    dimensions.validate().map_err(|errors| errors.join("; "))?;
+    return Ok(dimensions);
+}
+
+/// The delays on a `delays <are ms> <line clear ms>` line.
+fn parse_delays(line: &str) -> Result<Delays, String> {
+    let millis: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .map(|w| w.parse().ok())
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("invalid delays in `{}`", line))?;
+    match millis[..] {
+        [are, line_clear] => Ok(Delays {
+            are: Duration::from_millis(are),
+            line_clear: Duration::from_millis(line_clear),
+        }),
+        _ => Err(format!("invalid delays in `{}`", line)),
+    }
+}
+
+/// Feeds the inputs of a replay back frame by frame.
+pub struct Playback {
+    replay: Replay,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback {
+            replay: replay,
+            next: 0,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        return &self.replay;
+    }
+
+    /// The recorded inputs to apply before stepping `frame`. Frames must be
+    /// asked for in increasing order.
+    pub fn inputs_at(&mut self, frame: u64) -> Vec<Input> {
+        let mut inputs = vec![];
+        let events = self.replay.events();
+        while self.next < events.len() && events[self.next].frame <= frame {
+            inputs.push(events[self.next].input);
+            self.next += 1;
+        }
+        return inputs;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.next >= self.replay.events().len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays `game` for `frames` frames, applying an input every few frames
+    /// the way the window does, and records them in `replay`.
+    fn play_and_record(game: &mut Game, replay: &mut Replay, frames: u64) {
+        let inputs = [
+            Input::MoveLeft,
+            Input::RotateCw,
+            Input::HardDrop,
+            Input::MoveRight,
+            Input::Hold,
+            Input::SoftDrop,
+            Input::Rotate180,
+            Input::HardDrop,
+        ];
+        for frame in (0..frames).into_iter() {
+            if frame % 7 == 0 {
+                let input = inputs[(frame / 7) as usize % inputs.len()];
+                game.apply(input);
+                replay.record(frame, input);
+            }
+            game.step(FRAME_TIME);
+        }
+    }
+
+    /// Plays `replay` back on a fresh game for `frames` frames.
+    fn play_back(replay: Replay, frames: u64) -> Game {
+        let mut game = replay.new_game();
+        let mut playback = Playback::new(replay);
+        for frame in (0..frames).into_iter() {
+            for input in playback.inputs_at(frame) {
+                game.apply(input);
+            }
+            game.step(FRAME_TIME);
+        }
+        assert!(playback.is_finished());
+        return game;
+    }
+
+    #[test]
+    fn recorded_game_plays_back_the_same() {
+        let frames = 3000;
+        let mut game = Game::with_seed(RandomizerKind::Tgm, 99);
+        game.set_all_spin(true);
+        let mut replay = Replay::for_game(&game, Mode::Sprint);
+        play_and_record(&mut game, &mut replay, frames);
+        assert!(game.pieces() > 5);
+
+        let read = Replay::from_text(&replay.to_text()).unwrap();
+        assert_eq!(read, replay);
+        let played = play_back(read, frames);
+        assert_eq!(played.board().to_text(), game.board().to_text());
+        assert_eq!(played.score().points(), game.score().points());
+        assert_eq!(played.pieces(), game.pieces());
+        assert_eq!(played.is_active(), game.is_active());
+    }
+
+    #[test]
+    fn header_round_trips() {
+        let dimensions = Dimensions {
+            width: 8,
+            height: 16,
+            hidden_rows: 3,
+        };
+        let mut game = Game::with_dimensions(dimensions, RandomizerKind::Bag14, 7);
+        game.set_delays(Delays {
+            are: Duration::from_millis(50),
+            line_clear: Duration::from_millis(250),
+        });
+        let mut replay = Replay::for_game(&game, Mode::Ultra);
+        replay.record(12, Input::HardDrop);
+        let read = Replay::from_text(&replay.to_text()).unwrap();
+        assert_eq!(read, replay);
+        assert_eq!(read.new_game().board().dimensions(), dimensions);
+        assert_eq!(read.new_game().delays(), game.delays());
+    }
+
+    #[test]
+    fn old_replays_fall_back_to_defaults() {
+        let read = Replay::from_text("randomizer bag7\nseed 5\n30 hard_drop\n").unwrap();
+        assert_eq!(read.mode(), Mode::Marathon);
+        assert_eq!(read.new_game().delays(), Delays::none());
+        assert!(!read.new_game().all_spin());
+        assert!(Replay::from_text("seed 5\n").is_err());
+        assert!(Replay::from_text("randomizer bag7\nseed 5\n30 jump\n").is_err());
+    }
+}
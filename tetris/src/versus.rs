@@ -0,0 +1,499 @@
+//! Versus: two players side by side, each sending the other garbage by
+//! clearing lines. Started with `--versus` for two players on one keyboard,
+//! or with `--host <addr>` and `--join <addr>` across a network.
+//!
+//! Both games share a seed, so the players get the same minos. Garbage a
+//! player sends first cancels garbage queued against them, and the rest is
+//! queued against the opponent, rising on their next lock that clears
+//! nothing. The first player to top out loses.
+
+use nannou::prelude::*;
+use std::collections::HashSet;
+use std::io;
+use std::time::Duration;
+
+use crate::config::{Action, Bindings};
+use crate::engine::{Block, Board, Delays, Dimensions, Game, Phase};
+use crate::handling::{AutoRepeat, Handling};
+use crate::net::{Connection, Message, Snapshot};
+use crate::replay::FRAME_TIME;
+
+use super::{
+    arg_value, block_location, draw_board, draw_ghost, draw_mino, due_frames, fit_board,
+    frame_inputs, key_event, key_names, load_config, load_dealing, release_key, show_hold,
+    show_lines, show_next, show_overlay, Dealing, KeyEvent, State, BLOCK_SIZE, DEFAULT_PREVIEW_LEN,
+    T_BG,
+};
+
+const C_METER: Rgb8 = CRIMSON;
+
+struct Player {
+    game: Game,
+    auto_repeat: AutoRepeat,
+    bindings: Bindings,
+    wins: u32,
+}
+
+/// The opponent in a networked match, seen through the snapshots they send.
+struct Remote {
+    connection: Connection,
+    /// Whether this side hosts, and so starts the matches.
+    host: bool,
+    snapshot: Option<Snapshot>,
+    /// The last board sent, so only changes are sent.
+    sent_board: String,
+    over: bool,
+    wins: u32,
+    /// Why the connection was lost, once it was.
+    error: Option<String>,
+}
+
+pub struct Model {
+    /// Both players in local versus, only this side's in a networked match.
+    players: Vec<Player>,
+    remote: Option<Remote>,
+    dimensions: Dimensions,
+    delays: Delays,
+    all_spin: bool,
+    dealing: Dealing,
+    handling: Handling,
+    frame_timer: Duration,
+    held_keys: HashSet<Key>,
+    state: State,
+}
+
+pub fn model(_app: &App) -> Model {
+    let config = load_config();
+    let handling = config.handling;
+    let [bindings1, bindings2] = config.versus_bindings;
+    let player = |game: Game, bindings: Bindings| Player {
+        game: game,
+        auto_repeat: AutoRepeat::new(handling),
+        bindings: bindings,
+        wins: 0,
+    };
+    let remote = connect();
+    let dealing = load_dealing();
+    let (game1, game2) = new_games(config.dimensions, config.delays, config.all_spin, dealing);
+    let players = if remote.is_some() {
+        // Alone at the keyboard, so with the single-player keys.
+        vec![player(game1, config.bindings)]
+    } else {
+        vec![player(game1, bindings1), player(game2, bindings2)]
+    };
+    Model {
+        players: players,
+        remote: remote,
+        dimensions: config.dimensions,
+        delays: config.delays,
+        all_spin: config.all_spin,
+        dealing: dealing,
+        handling: handling,
+        frame_timer: Duration::from_secs(0),
+        held_keys: HashSet::new(),
+        state: State::Title,
+    }
+}
+
+/// Connects to the opponent given as `--host <addr>` or `--join <addr>`,
+/// waiting for them to join when hosting. Exits if that fails.
+fn connect() -> Option<Remote> {
+    let (connection, host) = match (arg_value("--host"), arg_value("--join")) {
+        (Some(addr), _) => {
+            eprintln!("waiting for an opponent on {}", addr);
+            (Connection::host(addr.as_str()), true)
+        }
+        (None, Some(addr)) => (Connection::join(addr.as_str()), false),
+        (None, None) => return None,
+    };
+    match connection {
+        Ok(connection) => Some(Remote {
+            connection: connection,
+            host: host,
+            snapshot: None,
+            sent_board: String::new(),
+            over: false,
+            wins: 0,
+            error: None,
+        }),
+        Err(err) => {
+            eprintln!("could not connect: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The seed of the garbage holes of `player` (0 or 1) in a match dealt
+/// from `seed`.
+fn garbage_seed(seed: u64, player: u64) -> u64 {
+    return seed.wrapping_add(player + 1);
+}
+
+/// Two games dealing the same minos, with garbage holes of their own.
+fn new_games(
+    dimensions: Dimensions,
+    delays: Delays,
+    all_spin: bool,
+    dealing: Dealing,
+) -> (Game, Game) {
+    let mut first = Game::with_dimensions(dimensions, dealing.kind, dealing.seed());
+    first.set_delays(delays);
+    first.set_all_spin(all_spin);
+    first.set_garbage_seed(garbage_seed(first.seed(), 0));
+    let mut second = Game::with_dimensions(dimensions, first.randomizer_kind(), first.seed());
+    second.set_delays(delays);
+    second.set_all_spin(all_spin);
+    second.set_garbage_seed(garbage_seed(first.seed(), 1));
+    return (first, second);
+}
+
+/// Starts the next match. Over the network only the host can, telling the
+/// opponent which minos to deal.
+fn restart(model: &mut Model) {
+    let (game1, game2) = new_games(
+        model.dimensions,
+        model.delays,
+        model.all_spin,
+        model.dealing,
+    );
+    let start = Message::Start {
+        kind: game1.randomizer_kind(),
+        seed: game1.seed(),
+        dimensions: model.dimensions,
+        delays: model.delays,
+        all_spin: model.all_spin,
+    };
+    match model.remote.as_mut() {
+        Some(remote) if remote.error.is_some() || !remote.host => {}
+        Some(remote) => match remote.connection.send(&start) {
+            Ok(()) => start_match(model, vec![game1]),
+            Err(err) => disconnect(model, err),
+        },
+        None => start_match(model, vec![game1, game2]),
+    }
+}
+
+fn start_match(model: &mut Model, games: Vec<Game>) {
+    for (player, game) in model.players.iter_mut().zip(games) {
+        player.game = game;
+        player.auto_repeat = AutoRepeat::new(model.handling);
+    }
+    if let Some(remote) = model.remote.as_mut() {
+        remote.snapshot = None;
+        remote.sent_board = String::new();
+        remote.over = false;
+    }
+    model.frame_timer = Duration::from_secs(0);
+    model.state = State::Playing;
+}
+
+fn disconnect(model: &mut Model, err: io::Error) {
+    if let Some(remote) = model.remote.as_mut() {
+        remote.error = Some(err.to_string());
+    }
+    model.state = State::GameOver;
+}
+
+fn press_action(model: &mut Model, player: usize, action: Action) {
+    let networked = model.remote.is_some();
+    match action.input() {
+        Some(input) if model.state == State::Playing => {
+            let player = &mut model.players[player];
+            if let Some(input) = player.auto_repeat.press(input) {
+                player.game.apply(input);
+            }
+        }
+        Some(_) => {}
+        None => match (action, model.state) {
+            // The opponent across the network would not wait.
+            (Action::Pause, State::Playing) if !networked => model.state = State::Paused,
+            (Action::Pause, State::Paused) => model.state = State::Playing,
+            (Action::Restart, State::Title) | (Action::Restart, State::GameOver) => restart(model),
+            _ => {}
+        },
+    }
+}
+
+pub fn event(_app: &App, model: &mut Model, event: Event) {
+    match event {
+        Event::WindowEvent {
+            id: _,
+            simple: Some(we),
+        } => window_event(model, we),
+        _other => {}
+    }
+}
+
+fn window_event(model: &mut Model, event: WindowEvent) {
+    match key_event(&mut model.held_keys, event) {
+        Some(KeyEvent::Pressed(key)) => {
+            for player in (0..model.players.len()).into_iter() {
+                if let Some(action) = model.players[player].bindings.action(key) {
+                    press_action(model, player, action);
+                }
+            }
+        }
+        Some(KeyEvent::Released(key)) => {
+            for player in model.players.iter_mut() {
+                release_key(&player.bindings, &mut player.auto_repeat, key);
+            }
+        }
+        None => {}
+    }
+}
+
+/// Steps the games in fixed frames, passing garbage between them after
+/// every frame.
+pub fn update(_app: &App, model: &mut Model, update: Update) {
+    if let Err(err) = receive_messages(model) {
+        disconnect(model, err);
+    }
+    if model.state != State::Playing {
+        return;
+    }
+    for _ in (0..due_frames(&mut model.frame_timer, update.since_last)).into_iter() {
+        for player in model.players.iter_mut() {
+            for input in frame_inputs(&mut player.auto_repeat, &player.game) {
+                player.game.apply(input);
+            }
+            player.game.step(FRAME_TIME);
+        }
+        if let Err(err) = exchange_garbage(model) {
+            disconnect(model, err);
+            return;
+        }
+        let lost = [has_lost(model, 0), has_lost(model, 1)];
+        if lost.contains(&true) {
+            for side in (0..2).into_iter() {
+                if !lost[side] {
+                    add_win(model, side);
+                }
+            }
+            model.state = State::GameOver;
+            break;
+        }
+    }
+    if let Err(err) = send_board(model) {
+        disconnect(model, err);
+    }
+}
+
+/// Handles what the opponent sent over the network since the last update.
+fn receive_messages(model: &mut Model) -> io::Result<()> {
+    let messages = match model.remote.as_mut() {
+        Some(remote) if remote.error.is_none() => remote.connection.receive()?,
+        _ => return Ok(()),
+    };
+    for message in messages {
+        match message {
+            Message::Start {
+                kind,
+                seed,
+                dimensions,
+                delays,
+                all_spin,
+            } => {
+                // The joining side plays the second player.
+                let mut game = Game::with_dimensions(dimensions, kind, seed);
+                game.set_delays(delays);
+                game.set_all_spin(all_spin);
+                game.set_garbage_seed(garbage_seed(seed, 1));
+                model.dimensions = dimensions;
+                model.delays = delays;
+                start_match(model, vec![game]);
+            }
+            Message::Garbage { lines } if model.state == State::Playing => {
+                model.players[0].game.receive_garbage(lines);
+            }
+            Message::Board(snapshot) => {
+                model.remote.as_mut().unwrap().snapshot = Some(snapshot);
+            }
+            Message::Over => model.remote.as_mut().unwrap().over = true,
+            Message::Hello { .. } | Message::Garbage { .. } => {}
+        }
+    }
+    return Ok(());
+}
+
+/// Passes the garbage each player attacks with to the other, and tells a
+/// networked opponent when this side topped out.
+fn exchange_garbage(model: &mut Model) -> io::Result<()> {
+    let attack = model.players[0].game.take_attack();
+    match model.remote.as_mut() {
+        Some(remote) => {
+            if attack > 0 {
+                remote
+                    .connection
+                    .send(&Message::Garbage { lines: attack })?;
+            }
+            if !model.players[0].game.is_active() {
+                remote.connection.send(&Message::Over)?;
+            }
+        }
+        None => {
+            let other = model.players[1].game.take_attack();
+            model.players[0].game.receive_garbage(other);
+            model.players[1].game.receive_garbage(attack);
+        }
+    }
+    return Ok(());
+}
+
+/// Sends a networked opponent a snapshot of the board if it changed.
+fn send_board(model: &mut Model) -> io::Result<()> {
+    let remote = match model.remote.as_mut() {
+        Some(remote) if remote.error.is_none() => remote,
+        _ => return Ok(()),
+    };
+    let message = Message::Board(Snapshot::of_game(&model.players[0].game));
+    let line = message.to_line();
+    if line != remote.sent_board {
+        remote.connection.send(&message)?;
+        remote.sent_board = line;
+    }
+    return Ok(());
+}
+
+/// Whether `side`, 0 on the left and 1 on the right, topped out.
+fn has_lost(model: &Model, side: usize) -> bool {
+    match (side, &model.remote) {
+        (1, Some(remote)) => remote.over,
+        _ => !model.players[side].game.is_active(),
+    }
+}
+
+fn add_win(model: &mut Model, side: usize) {
+    match (side, model.remote.as_mut()) {
+        (1, Some(remote)) => remote.wins += 1,
+        _ => model.players[side].wins += 1,
+    }
+}
+
+pub fn view(app: &App, model: &Model, frame: Frame) {
+    frame.clear(T_BG);
+    let window = app.window_rect();
+    for side in (0..2).into_iter() {
+        let area = Rect::from_x_y_w_h(
+            window.left() + window.w() * (side as f32 + 0.5) / 2.0,
+            window.y(),
+            window.w() / 2.0,
+            window.h(),
+        );
+        let draw = fit_board(app.draw(), area, model.dimensions);
+        match (side, &model.remote) {
+            (1, Some(remote)) => view_remote(remote, model, &draw),
+            _ => view_player(side, &model.players[side], model, &draw),
+        }
+    }
+    app.draw().to_frame(app, &frame).unwrap();
+}
+
+fn view_player(side: usize, player: &Player, model: &Model, draw: &Draw) {
+    let game = &player.game;
+    let board = game.board();
+    draw_board(board, game.phase(), game.delays(), draw);
+    if game.is_active() && game.is_falling() && model.state != State::Title {
+        draw_ghost(&game.ghost(), draw);
+        draw_mino(game.mino(), draw);
+    }
+    show_hold(game.hold(), game.can_hold(), board, draw);
+    show_next(&game.preview(DEFAULT_PREVIEW_LEN), board, draw);
+    draw_garbage_meter(game.pending_garbage(), board.height(), draw);
+    let mut lines = vec![
+        side_name(side, model),
+        format!("WINS {}", player.wins),
+        format!("SENT {}", game.sent()),
+        format!("LINES {}", game.score().lines()),
+    ];
+    if let Some(clear) = game.score().last_clear() {
+        lines.push(clear.name());
+        if clear.perfect {
+            lines.push("PERFECT CLEAR".to_string());
+        }
+    }
+    show_side(side, &lines, board, model, draw);
+}
+
+/// Draws the last snapshot the networked opponent sent, or an empty board
+/// before the first.
+fn view_remote(remote: &Remote, model: &Model, draw: &Draw) {
+    let mut lines = vec![side_name(1, model), format!("WINS {}", remote.wins)];
+    let board = match &remote.snapshot {
+        Some(snapshot) => {
+            draw_garbage_meter(snapshot.pending, snapshot.board.height(), draw);
+            lines.push(format!("SENT {}", snapshot.sent));
+            lines.push(format!("LINES {}", snapshot.lines));
+            snapshot.board.clone()
+        }
+        None => Board::from_rows(model.dimensions, &[]),
+    };
+    draw_board(&board, &Phase::Falling, Delays::none(), draw);
+    show_side(1, &lines, &board, model, draw);
+}
+
+fn side_name(side: usize, model: &Model) -> String {
+    match (side, &model.remote) {
+        (0, Some(_)) => "YOU".to_string(),
+        (_, Some(_)) => "OPPONENT".to_string(),
+        (_, None) => format!("PLAYER {}", side + 1),
+    }
+}
+
+/// Writes `lines` left of the board of `side`, and the overlay of the
+/// current state over it.
+fn show_side(side: usize, lines: &[String], board: &Board, model: &Model, draw: &Draw) {
+    show_lines(lines, board, draw);
+    let restart_key = key_names(&model.players[0].bindings, Action::Restart);
+    let waiting = match &model.remote {
+        Some(remote) => !remote.host,
+        None => false,
+    };
+    let prompt = |what: &str| {
+        if waiting {
+            "Waiting for the host".to_string()
+        } else {
+            format!("Press {} to {}", restart_key, what)
+        }
+    };
+    match model.state {
+        State::Title => show_overlay(
+            &[
+                "VERSUS".to_string(),
+                side_name(side, model),
+                prompt("start"),
+            ],
+            board,
+            draw,
+        ),
+        State::Paused => show_overlay(&["PAUSED".to_string()], board, draw),
+        State::GameOver => {
+            let lines = match model.remote.as_ref().and_then(|r| r.error.as_ref()) {
+                Some(error) => vec!["DISCONNECTED".to_string(), error.to_uppercase()],
+                None => {
+                    let result = match (has_lost(model, side), has_lost(model, 1 - side)) {
+                        (false, true) => "WIN",
+                        (true, false) => "LOSE",
+                        _ => "DRAW",
+                    };
+                    vec![result.to_string(), prompt("play again")]
+                }
+            };
+            show_overlay(&lines, board, draw);
+        }
+        State::Playing | State::ConfirmRestart { .. } => {}
+    }
+}
+
+/// A bar left of the board, as tall as the garbage waiting to rise.
+fn draw_garbage_meter(pending: u32, height: usize, draw: &Draw) {
+    if pending == 0 {
+        return;
+    }
+    let rows = (pending as usize).min(height) as f32;
+    let (x, _) = block_location(&Block { x: -1, y: 0 });
+    draw.rect()
+        .x_y(x + BLOCK_SIZE / 4.0, BLOCK_SIZE * (rows - 1.0) / 2.0)
+        .w(BLOCK_SIZE / 4.0)
+        .h(BLOCK_SIZE * rows)
+        .color(C_METER);
+}